@@ -0,0 +1,612 @@
+// Actions module for mapping keyboard, mouse and gamepad input onto board actions
+use bevy::{prelude::*, input::{InputSystem, mouse::MouseWheel, touch::Touches}, render::view::RenderLayers, utils::{HashMap, HashSet}, window::{CursorMoved, PrimaryWindow}};
+use serde::{Deserialize, Serialize};
+
+use crate::derivables::*;
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_resource::<ActionBindings>()
+			.init_resource::<Actions>()
+			.init_resource::<Pointer>()
+			// Everything downstream reads actions, so work them out straight after Bevy reads input
+			.add_systems(PreUpdate, (
+				update_actions,
+				update_pointer,
+			).chain().after(InputSystem))
+			.add_systems(OnTransition{from: GameState::Boot, to: GameState::Game}, (
+				spawn_board_cursor,
+			))
+			.add_systems(Update, (
+				draw_board_cursor,
+			).run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+// Held modifier keys have to match exactly, so shift + right click doesn't also draw a thread
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize, Serialize)]
+pub enum Modifier {
+	#[default]
+	None,
+	Shift,
+	Ctrl,
+	CtrlShift,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+pub enum InputSource {
+	Key(KeyCode),
+	Mouse(MouseButton),
+	// Any connected gamepad, modifiers are ignored
+	Pad(GamepadButtonType),
+	WheelUp,
+	WheelDown,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+pub struct Binding {
+	pub input: InputSource,
+	#[serde(default)]
+	pub modifier: Modifier,
+}
+
+impl Binding {
+	pub fn new(input: InputSource) -> Self {
+		Self {
+			input: input,
+			modifier: Modifier::None,
+		}
+	}
+
+	pub fn with(input: InputSource, modifier: Modifier) -> Self {
+		Self {
+			input: input,
+			modifier: modifier,
+		}
+	}
+
+	// How the controls screen writes it out
+	pub fn get_name(&self) -> String {
+		let input = match self.input {
+			InputSource::Key(key) => format!("{:?}", key),
+			InputSource::Mouse(MouseButton::Left) => "Left Click".to_string(),
+			InputSource::Mouse(MouseButton::Right) => "Right Click".to_string(),
+			InputSource::Mouse(MouseButton::Middle) => "Middle Click".to_string(),
+			InputSource::Mouse(MouseButton::Other(i)) => format!("Mouse {}", i),
+			InputSource::Pad(button_type) => format!("Pad {:?}", button_type),
+			InputSource::WheelUp => "Wheel Up".to_string(),
+			InputSource::WheelDown => "Wheel Down".to_string(),
+		};
+		match self.modifier {
+			Modifier::None => input,
+			Modifier::Shift => format!("Shift + {}", input),
+			Modifier::Ctrl => format!("Ctrl + {}", input),
+			Modifier::CtrlShift => format!("Ctrl + Shift + {}", input),
+		}
+	}
+}
+
+// Bindings for some actions, each listed action loses all of its old bindings
+pub type BindingMap = HashMap<Action, Vec<Binding>>;
+
+// Any number of bindings can trigger each action
+#[derive(Resource)]
+pub struct ActionBindings(pub Vec<(Action, Binding)>);
+
+impl Default for ActionBindings {
+	fn default() -> Self {
+		use InputSource::*;
+		Self([
+			(Action::Up, Binding::new(Key(KeyCode::Up))),
+			(Action::Up, Binding::new(Key(KeyCode::W))),
+			(Action::Up, Binding::new(Pad(GamepadButtonType::DPadUp))),
+			(Action::Down, Binding::new(Key(KeyCode::Down))),
+			(Action::Down, Binding::new(Key(KeyCode::S))),
+			(Action::Down, Binding::new(Pad(GamepadButtonType::DPadDown))),
+			(Action::Left, Binding::new(Key(KeyCode::Left))),
+			(Action::Left, Binding::new(Key(KeyCode::A))),
+			(Action::Left, Binding::new(Pad(GamepadButtonType::DPadLeft))),
+			(Action::Right, Binding::new(Key(KeyCode::Right))),
+			(Action::Right, Binding::new(Key(KeyCode::D))),
+			(Action::Right, Binding::new(Pad(GamepadButtonType::DPadRight))),
+			(Action::Select, Binding::new(Mouse(MouseButton::Left))),
+			(Action::Select, Binding::new(Key(KeyCode::Space))),
+			(Action::Select, Binding::new(Pad(GamepadButtonType::South))),
+			(Action::Thread, Binding::new(Mouse(MouseButton::Right))),
+			(Action::Thread, Binding::new(Key(KeyCode::E))),
+			(Action::Thread, Binding::new(Pad(GamepadButtonType::West))),
+			(Action::Cancel, Binding::new(Key(KeyCode::Escape))),
+			(Action::Cancel, Binding::new(Pad(GamepadButtonType::East))),
+			(Action::Unravel, Binding::with(Mouse(MouseButton::Right), Modifier::Shift)),
+			(Action::Unravel, Binding::new(Key(KeyCode::X))),
+			(Action::Unravel, Binding::new(Pad(GamepadButtonType::North))),
+			(Action::Rotate, Binding::new(Key(KeyCode::R))),
+			(Action::Rotate, Binding::new(WheelUp)),
+			(Action::Rotate, Binding::new(Pad(GamepadButtonType::RightTrigger))),
+			(Action::RotateBack, Binding::new(WheelDown)),
+			(Action::RotateBack, Binding::new(Pad(GamepadButtonType::LeftTrigger))),
+			(Action::Flip, Binding::new(Key(KeyCode::Q))),
+			(Action::Flip, Binding::new(Pad(GamepadButtonType::RightThumb))),
+			(Action::FocusButtons, Binding::new(Key(KeyCode::Tab))),
+			(Action::FocusButtons, Binding::new(Pad(GamepadButtonType::Select))),
+			(Action::Undo, Binding::with(Key(KeyCode::Z), Modifier::Ctrl)),
+			(Action::Undo, Binding::new(Pad(GamepadButtonType::LeftTrigger2))),
+			(Action::Redo, Binding::with(Key(KeyCode::Y), Modifier::Ctrl)),
+			(Action::Redo, Binding::with(Key(KeyCode::Z), Modifier::CtrlShift)),
+			(Action::Redo, Binding::new(Pad(GamepadButtonType::RightTrigger2))),
+			(Action::Filter, Binding::new(Key(KeyCode::P))),
+			(Action::Controls, Binding::new(Key(KeyCode::F1))),
+			(Action::Controls, Binding::new(Pad(GamepadButtonType::Start))),
+			(Action::ZoomIn, Binding::new(WheelUp)),
+			(Action::ZoomIn, Binding::new(Key(KeyCode::Equals))),
+			(Action::ZoomOut, Binding::new(WheelDown)),
+			(Action::ZoomOut, Binding::new(Key(KeyCode::Minus))),
+			(Action::Pan, Binding::new(Mouse(MouseButton::Middle))),
+			(Action::Editor, Binding::new(Key(KeyCode::F2))),
+			(Action::EditorPlace, Binding::new(Mouse(MouseButton::Left))),
+			(Action::EditorDelete, Binding::new(Mouse(MouseButton::Right))),
+			(Action::EditorDelete, Binding::new(Key(KeyCode::Delete))),
+			(Action::EditorNextTile, Binding::new(Key(KeyCode::Tab))),
+			(Action::EditorPrevTile, Binding::with(Key(KeyCode::Tab), Modifier::Shift)),
+			(Action::EditorRotate, Binding::new(Key(KeyCode::R))),
+			(Action::EditorMirror, Binding::new(Key(KeyCode::Q))),
+			(Action::EditorLock, Binding::new(Key(KeyCode::K))),
+			(Action::EditorTack, Binding::new(Key(KeyCode::T))),
+			(Action::EditorGroup, Binding::new(Key(KeyCode::G))),
+			(Action::EditorLinks, Binding::new(Key(KeyCode::L))),
+			(Action::EditorSolution, Binding::new(Key(KeyCode::S))),
+			(Action::EditorCase(0), Binding::new(Key(KeyCode::Key1))),
+			(Action::EditorCase(1), Binding::new(Key(KeyCode::Key2))),
+			(Action::EditorCase(2), Binding::new(Key(KeyCode::Key3))),
+			(Action::EditorCase(3), Binding::new(Key(KeyCode::Key4))),
+			(Action::EditorCase(4), Binding::new(Key(KeyCode::Key5))),
+			(Action::EditorCase(5), Binding::new(Key(KeyCode::Key6))),
+			(Action::EditorAddCase, Binding::new(Key(KeyCode::Equals))),
+			(Action::EditorRemoveCase, Binding::new(Key(KeyCode::Minus))),
+			(Action::EditorReport, Binding::new(Key(KeyCode::Return))),
+			(Action::EditorBlocking, Binding::new(Key(KeyCode::B))),
+			(Action::EditorSave, Binding::with(Key(KeyCode::S), Modifier::Ctrl)),
+		].to_vec())
+	}
+}
+
+impl ActionBindings {
+	pub fn get(&self, action: Action) -> Vec<Binding> {
+		self.0.iter().filter(|(bound, _)| *bound == action).map(|(_, binding)| *binding).collect()
+	}
+
+	pub fn apply(&mut self, overrides: &BindingMap) {
+		self.0.retain(|(action, _)| !overrides.contains_key(action));
+		for action in ACTIONS.into_iter().chain(EDITOR_ACTIONS) {
+			for binding in overrides.get(&action).into_iter().flatten() {
+				self.0.push((action, *binding));
+			}
+		}
+	}
+
+	// Actions other than this one that the binding already triggers
+	pub fn clashes(&self, action: Action, binding: Binding) -> Vec<Action> {
+		let mut clashes = Vec::new();
+		for (other, other_binding) in self.0.iter() {
+			if *other != action && *other_binding == binding && action.overlaps(*other) && !clashes.contains(other) {
+				clashes.push(*other);
+			}
+		}
+		clashes
+	}
+
+	// Every binding shared by more than one action
+	pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+		let mut conflicts: Vec<(Binding, Vec<Action>)> = Vec::new();
+		for (action, binding) in self.0.iter() {
+			if conflicts.iter().any(|(conflict, _)| conflict == binding) {continue;};
+			let clashes = self.clashes(*action, *binding);
+			if !clashes.is_empty() {
+				conflicts.push((*binding, [*action].into_iter().chain(clashes).collect()));
+			}
+		}
+		conflicts
+	}
+}
+
+pub fn held_modifier(
+	keyboard: &Res<Input<KeyCode>>,
+) -> Modifier {
+	let shift = keyboard.pressed(KeyCode::ShiftLeft) || keyboard.pressed(KeyCode::ShiftRight);
+	let ctrl = keyboard.pressed(KeyCode::ControlLeft) || keyboard.pressed(KeyCode::ControlRight);
+	match (ctrl, shift) {
+		(false, false) => Modifier::None,
+		(false, true) => Modifier::Shift,
+		(true, false) => Modifier::Ctrl,
+		(true, true) => Modifier::CtrlShift,
+	}
+}
+
+// How far a stick has to lean to move the board cursor
+const STICK_THRESHOLD: f32 = 0.5;
+// Holding a finger still this long picks up a tile
+const LONG_PRESS: f32 = 0.4;
+// How far a finger can wander and still tap, in window pixels
+const TAP_SLOP: f32 = 12.0;
+// How far two fingers can spread or close and still tap together, more is a pinch
+const PINCH_SLOP: f32 = 6.0;
+
+// Touches from the first finger down until the last one lifts
+#[derive(Default)]
+struct TouchGesture {
+	started: Option<f32>,
+	// Most fingers down at once
+	fingers: usize,
+	// Wandered too far to be a tap or long press
+	moved: bool,
+	long_press: bool,
+	// Distance between the fingers when the second one landed
+	spread: Option<f32>,
+}
+
+fn update_actions(
+	mut actions: ResMut<Actions>,
+	mut ev_r_scroll: EventReader<MouseWheel>,
+	mut gesture: Local<TouchGesture>,
+	bindings: Res<ActionBindings>,
+	keyboard: Res<Input<KeyCode>>,
+	mouse: Res<Input<MouseButton>>,
+	touches: Res<Touches>,
+	gamepads: Res<Gamepads>,
+	gamepad_buttons: Res<Input<GamepadButton>>,
+	gamepad_axes: Res<Axis<GamepadAxis>>,
+	view_camera_query: Query<(&Camera, &GlobalTransform), With<ViewCamera>>,
+	board_camera_query: Query<(&Transform, &OrthographicProjection), With<BoardCamera>>,
+	case_report_stack: Res<CaseReportStack>,
+	board: Res<BoardConfig>,
+	time: Res<Time>,
+) {
+	let modifier = held_modifier(&keyboard);
+	let mut scroll = 0.0;
+	for ev in ev_r_scroll.read() {
+		scroll += ev.y;
+	}
+
+	let mut held = HashSet::new();
+	let mut taps = HashSet::new();
+	for (action, binding) in bindings.0.iter() {
+		let modifier_held = binding.modifier == modifier;
+		match binding.input {
+			InputSource::Key(key) => if modifier_held && keyboard.pressed(key) {held.insert(*action);},
+			InputSource::Mouse(button) => if modifier_held && mouse.pressed(button) {held.insert(*action);},
+			InputSource::Pad(button_type) => {
+				if gamepads.iter().any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type))) {
+					held.insert(*action);
+				}
+			}
+			InputSource::WheelUp => if modifier_held && scroll > 0.0 {taps.insert(*action);},
+			InputSource::WheelDown => if modifier_held && scroll < 0.0 {taps.insert(*action);},
+		}
+	}
+
+	// Either stick moves the board cursor like the d-pad
+	for gamepad in gamepads.iter() {
+		for (x_axis, y_axis) in [
+			(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+			(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+		] {
+			let x = gamepad_axes.get(GamepadAxis::new(gamepad, x_axis)).unwrap_or(0.0);
+			let y = gamepad_axes.get(GamepadAxis::new(gamepad, y_axis)).unwrap_or(0.0);
+			if x > STICK_THRESHOLD {held.insert(Action::Right);};
+			if x < -STICK_THRESHOLD {held.insert(Action::Left);};
+			if y > STICK_THRESHOLD {held.insert(Action::Up);};
+			if y < -STICK_THRESHOLD {held.insert(Action::Down);};
+		}
+	}
+
+	// Taps hold their action for a single frame, so they press and release like a quick click
+	let now = time.elapsed_seconds();
+	if touches.any_just_pressed() && gesture.started.is_none() {
+		*gesture = TouchGesture {started: Some(now), ..default()};
+	}
+	if let Some(started) = gesture.started {
+		gesture.fingers = gesture.fingers.max(touches.iter().count());
+		gesture.moved |= touches.iter().chain(touches.iter_just_released()).any(|touch| touch.distance().length() > TAP_SLOP)
+			|| touches.iter_just_canceled().next().is_some();
+		let down: Vec<Vec2> = touches.iter().map(|touch| touch.position()).collect();
+		if let [first, second] = down[..] {
+			let spread = first.distance(second);
+			let landed = *gesture.spread.get_or_insert(spread);
+			gesture.moved |= (spread - landed).abs() > PINCH_SLOP;
+		}
+		let quick = now - started < LONG_PRESS;
+		if gesture.fingers == 1 && !gesture.moved && !quick {
+			gesture.long_press = true;
+		}
+		if touches.iter().next().is_some() {
+			if gesture.long_press {
+				held.insert(Action::Select);
+			}
+		} else {
+			if !gesture.long_press && !gesture.moved && quick {
+				let lifted: Vec<Vec2> = touches.iter_just_released().map(|touch| touch.position()).collect();
+				match gesture.fingers {
+					// Tacks on the board take taps as threads, everything else as a select
+					1 => {
+						let board_view = board_camera_query.get_single().map_or(BoardView::default(), |(camera_pos, projection)| BoardView::new(camera_pos, projection));
+						let tapped = view_camera_query.get_single().ok().and_then(|view_camera| fingers_to_world(&lifted, view_camera, &board_view));
+						let board_tap = tapped.is_some_and(|(pos, _)| board.contains(pos)) && !case_report_stack.hovered;
+						held.insert(if board_tap {Action::Thread} else {Action::Select});
+					}
+					2 => {held.insert(Action::Unravel);},
+					_ => (),
+				}
+			}
+			*gesture = TouchGesture::default();
+		}
+	}
+
+	actions.update(held, taps);
+}
+
+// Holding a direction steps the board cursor again after a pause
+const REPEAT_DELAY: f32 = 0.35;
+const REPEAT_RATE: f32 = 0.08;
+
+// Where a spot on the window lands on the board, through the zoomed board camera when it's over the board view,
+// and where it is in the fixed view
+fn window_to_world(
+	window_pos: Vec2,
+	(view_camera, view_camera_pos): (&Camera, &GlobalTransform),
+	board_view: &BoardView,
+) -> Option<(Vec2, Vec2)> {
+	let ui_pos = view_camera.viewport_to_world_2d(view_camera_pos, window_pos)?;
+	Some((board_view.from_ui(ui_pos).unwrap_or(ui_pos), ui_pos))
+}
+
+// Middle of the fingers, so two finger gestures act between them
+fn fingers_to_world(
+	fingers: &[Vec2],
+	view_camera: (&Camera, &GlobalTransform),
+	board_view: &BoardView,
+) -> Option<(Vec2, Vec2)> {
+	if fingers.is_empty() {return None;};
+	let middle = fingers.iter().sum::<Vec2>() / fingers.len() as f32;
+	window_to_world(middle, view_camera, board_view)
+}
+
+fn update_pointer(
+	mut pointer: ResMut<Pointer>,
+	mut ev_r_cursor: EventReader<CursorMoved>,
+	mut repeat: Local<f32>,
+	actions: Res<Actions>,
+	touches: Res<Touches>,
+	button_query: Query<&Transform, With<UIButton>>,
+	window_query: Query<&Window, With<PrimaryWindow>>,
+	view_camera_query: Query<(&Camera, &GlobalTransform), With<ViewCamera>>,
+	board_camera_query: Query<(&Transform, &OrthographicProjection), With<BoardCamera>>,
+	board: Res<BoardConfig>,
+	time: Res<Time>,
+) {
+	let Ok(window) = window_query.get_single() else {return};
+	let Ok(view_camera) = view_camera_query.get_single() else {return};
+	let board_view = board_camera_query.get_single().map_or(BoardView::default(), |(camera_pos, projection)| BoardView::new(camera_pos, projection));
+	// Touching the mouse hands control back to it
+	if ev_r_cursor.read().count() > 0 {
+		pointer.snapped = false;
+		pointer.touch = false;
+	}
+	// Fingers that just lifted still count, taps land where they were
+	let mut fingers: Vec<Vec2> = touches.iter().map(|touch| touch.position()).collect();
+	if fingers.is_empty() {
+		fingers = touches.iter_just_released().map(|touch| touch.position()).collect();
+	}
+	if let Some((touch_pos, touch_ui)) = fingers_to_world(&fingers, view_camera, &board_view) {
+		pointer.pos = Some(touch_pos);
+		pointer.ui = Some(touch_ui);
+		pointer.snapped = false;
+		pointer.touch = true;
+	}
+	if !pointer.snapped && !pointer.touch {
+		let cursor = window.cursor_position().and_then(|cursor_pos| window_to_world(cursor_pos, view_camera, &board_view));
+		pointer.pos = cursor.map(|(cursor_pos, _)| cursor_pos);
+		pointer.ui = cursor.map(|(_, cursor_ui)| cursor_ui);
+	}
+
+	let mut step = Vec2::ZERO;
+	for (action, direction) in [
+		(Action::Up, Vec2::Y), (Action::Down, Vec2::NEG_Y),
+		(Action::Left, Vec2::NEG_X), (Action::Right, Vec2::X),
+	] {
+		if actions.pressed(action) {
+			step += direction;
+		}
+	}
+	let fresh = [Action::Up, Action::Down, Action::Left, Action::Right].iter().any(|action| actions.just_pressed(*action));
+	let mut moving = false;
+	if fresh {
+		*repeat = 0.0;
+		moving = true;
+	} else if step != Vec2::ZERO {
+		*repeat += time.delta_seconds();
+		if *repeat > REPEAT_DELAY {
+			*repeat -= REPEAT_RATE;
+			moving = true;
+		}
+	}
+	if moving && step != Vec2::ZERO {
+		let from = pointer.pos.unwrap_or(board_view.centre);
+		pointer.pos = Some(step_cursor(&board, &board_view, from, step));
+		pointer.snapped = true;
+		pointer.touch = false;
+	}
+
+	// Step through the buttons top to bottom
+	if actions.just_pressed(Action::FocusButtons) {
+		let mut buttons: Vec<Vec2> = button_query.iter().map(|button_pos| button_pos.translation.xy()).collect();
+		buttons.sort_by(|a, b| (-a.y, a.x).partial_cmp(&(-b.y, b.x)).unwrap());
+		let current = pointer.pos.and_then(|pos| buttons.iter().position(|button| *button == pos));
+		let next = current.map_or(0, |current| (current + 1) % buttons.len());
+		if let Some(button) = buttons.get(next) {
+			pointer.pos = Some(*button);
+			pointer.snapped = true;
+			pointer.touch = false;
+		}
+	}
+	if pointer.snapped {
+		pointer.ui = pointer.pos.map(|pos| board_view.to_ui(pos));
+	}
+}
+
+// Centre of the nearest cell inside an area, lined up with the evidence board's grid
+fn snap_to_cell(
+	board: &BoardConfig,
+	loc: Vec2,
+	area: Rect,
+) -> Vec2 {
+	let origin = board.origin - board.size()/2.0 + CELL_SIZE/2.0;
+	// Stepping past the edge of the area lands on the last cell inside it
+	let min = ((area.min + CELL_SIZE/2.0 - origin) / CELL_SIZE).ceil() * CELL_SIZE + origin;
+	let max = ((area.max - CELL_SIZE/2.0 - origin) / CELL_SIZE).floor() * CELL_SIZE + origin;
+	(((loc - origin) / CELL_SIZE).round() * CELL_SIZE + origin).clamp(min, max.max(min))
+}
+
+// Move the board cursor a cell, over the part of the board the board camera shows and the fixed view around it
+fn step_cursor(
+	board: &BoardConfig,
+	board_view: &BoardView,
+	from: Vec2,
+	step: Vec2,
+) -> Vec2 {
+	let shown = board_view.visible().intersect(Rect::from_center_size(board.origin, board.size()));
+	let screen = Rect::from_center_size(Vec2::ZERO, VIEW_SIZE);
+	let on_board = board_view.visible().contains(from) || board.contains(from);
+	let mut ui_from = from;
+	if on_board {
+		// A cursor left behind by zooming or panning comes back to the nearest cell in view
+		let to = snap_to_cell(board, from + step * CELL_SIZE, shown);
+		if to != from {return to;};
+		ui_from = board_view.to_ui(from);
+	}
+	let ui_to = snap_to_cell(board, ui_from + step * CELL_SIZE, screen);
+	match board_view.from_ui(ui_to) {
+		// Zoomed in, the edge of the board view holds the cursor
+		Some(_) if on_board => from,
+		Some(to) => snap_to_cell(board, to, shown),
+		None => ui_to,
+	}
+}
+
+fn spawn_board_cursor(
+	mut commands: Commands,
+) {
+	commands.spawn((SpriteBundle{
+		transform: Transform::from_xyz(0.0, 0.0, 970.0),
+		sprite: Sprite {
+			custom_size: Some(Vec2::new(CELL_SIZE, CELL_SIZE)),
+			color: Color::rgba(1.0, 1.0, 0.4, 0.4),
+			..default()
+		},
+		visibility: Visibility::Hidden,
+		..default()
+		},
+		BoardCursor,
+		RenderLayers::default(),
+	));
+}
+
+// Only shown while keys or a gamepad are moving it
+fn draw_board_cursor(
+	mut cursor_query: Query<(&mut Transform, &mut Visibility, &mut RenderLayers), With<BoardCursor>>,
+	pointer: Res<Pointer>,
+) {
+	if !pointer.is_changed() {return;};
+	for (mut cursor_pos, mut visibility, mut layers) in cursor_query.iter_mut() {
+		match pointer.pos.filter(|_| pointer.snapped) {
+			Some(pos) => {
+				cursor_pos.translation = pos.extend(cursor_pos.translation.z);
+				*layers = pointer.layer();
+				*visibility = Visibility::Visible;
+			}
+			None => *visibility = Visibility::Hidden,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::Rng;
+	use crate::testing::check_cases;
+
+	const STEPS: [Vec2; 4] = [Vec2::Y, Vec2::NEG_Y, Vec2::X, Vec2::NEG_X];
+
+	fn on_grid(
+		board: &BoardConfig,
+		loc: Vec2,
+	) -> bool {
+		let cells = (loc - board.top_left() - Vec2::new(H_CELL_SIZE, -H_CELL_SIZE)) / CELL_SIZE;
+		(cells - cells.round()).abs().max_element() < 1e-3
+	}
+
+	#[test]
+	fn default_bindings_only_share_inputs_between_actions_that_cant_clash() {
+		assert_eq!(ActionBindings::default().conflicts(), Vec::new());
+		// The editor reuses the board's keys
+		let bindings = ActionBindings::default();
+		assert!(bindings.clashes(Action::EditorRotate, Binding::new(InputSource::Key(KeyCode::R))).is_empty());
+		assert_eq!(bindings.clashes(Action::EditorRotate, Binding::new(InputSource::Key(KeyCode::Q))), vec![Action::EditorMirror]);
+	}
+
+	#[test]
+	fn cursor_crosses_between_the_board_and_the_workbench() {
+		let board = BoardConfig::default();
+		let board_view = BoardView::default();
+		let bottom_row = Vec2::new(-740.0, -20.0);
+		let workbench = step_cursor(&board, &board_view, bottom_row, Vec2::NEG_Y);
+		assert_eq!(workbench, Vec2::new(-740.0, -60.0));
+		assert_eq!(step_cursor(&board, &board_view, workbench, Vec2::Y), bottom_row);
+	}
+
+	#[test]
+	fn zoomed_in_cursor_stays_on_the_board_in_view() {
+		let board = BoardConfig::default();
+		let board_view = BoardView {centre: Vec2::new(-300.0, 250.0), scale: 0.5};
+		let shown = board_view.visible();
+		for step in STEPS {
+			let mut cursor = board_view.centre;
+			for _ in 0..40 {
+				cursor = step_cursor(&board, &board_view, cursor, step);
+				assert!(shown.contains(cursor), "{:?} left the view going {:?}", cursor, step);
+			}
+		}
+		// Coming up off the workbench lands on a cell in view
+		let entered = step_cursor(&board, &board_view, Vec2::new(-740.0, -60.0), Vec2::Y);
+		assert!(shown.contains(entered) && on_grid(&board, entered), "{:?}", entered);
+	}
+
+	#[test]
+	fn cursor_only_lands_where_it_can_be_seen() {
+		let board = BoardConfig::default();
+		let screen = Rect::from_center_size(Vec2::ZERO, VIEW_SIZE);
+		let board_rect = Rect::from_center_size(BOARD_VIEW_CENTRE, BOARD_VIEW_SIZE);
+		let mut cursor = board.origin;
+		let mut board_view = BoardView::default();
+		check_cases(21, |rng| {
+			// Zoom and pan now and again, keeping the view on the board like the camera does
+			if rng.gen_ratio(1, 50) {
+				let scale = rng.gen_range(0.4..=1.0);
+				let slack = BOARD_VIEW_SIZE / 2.0 * (1.0 - scale);
+				let centre = BOARD_VIEW_CENTRE + Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * slack;
+				board_view = BoardView {centre: centre, scale: scale};
+			}
+			let step = STEPS[rng.gen_range(0..STEPS.len())];
+			cursor = step_cursor(&board, &board_view, cursor, step);
+			assert!(on_grid(&board, cursor), "{:?} is off the grid", cursor);
+			if board_rect.contains(cursor) {
+				assert!(board_view.visible().contains(cursor), "{:?} is out of view of {:?}", cursor, board_view);
+			} else {
+				assert!(screen.contains(cursor), "{:?} is off screen", cursor);
+			}
+		});
+	}
+}
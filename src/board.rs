@@ -0,0 +1,332 @@
+// Board module, a plain model of the evidence board that can be checked without a running app
+use crate::derivables::TileType;
+
+#[derive(Clone, Debug, Default)]
+pub struct Board {
+	pub tiles: Vec<BoardTile>,
+	pub tacks: Vec<BoardTack>,
+	pub threads: Vec<BoardThread>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BoardTile {
+	pub tile_type: TileType,
+	// Grid index if the tile is pinned to the board, None if it's on the workbench
+	pub cell: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BoardTack {
+	// Index into Board::tiles
+	pub tile: usize,
+	pub group: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct BoardThread {
+	pub group: usize,
+	// Indices into Board::tacks, a single tack means the thread is still loose
+	// Routing pins it bends around sit between the two ends and stay in PIN_GROUP
+	pub tacks: Vec<usize>,
+}
+
+// Suspect and evidence required for each case, in case order
+#[derive(Clone, Debug, Default)]
+pub struct Solution {
+	pub cases: Vec<Vec<TileType>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CaseVerdict {
+	// Every required tile threaded in and no other suspect, extra evidence and loose ends don't count against it
+	pub solved: bool,
+	// Required suspect or evidence that isn't threaded into the case
+	pub missing: Vec<TileType>,
+	// Suspect threaded into the case that isn't the culprit
+	pub wrong_suspect: Option<TileType>,
+	// Evidence threaded into the case that doesn't belong to it
+	pub extra: Vec<TileType>,
+	// Thread ends that stop short of a suspect
+	pub unterminated: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Verdict {
+	pub cases: Vec<CaseVerdict>,
+}
+
+impl Board {
+	// Tile types whose tacks have been threaded into the group
+	pub fn group_evidence(&self, group: usize) -> Vec<TileType> {
+		self.tacks.iter()
+			.filter(|tack| tack.group == group)
+			.map(|tack| self.tiles[tack.tile].tile_type)
+			.collect()
+	}
+
+	// Count thread ends in the group that neither carry on into another thread nor reach a suspect
+	pub fn unterminated_threads(&self, group: usize) -> usize {
+		let threads: Vec<&BoardThread> = self.threads.iter().filter(|thread| thread.group == group).collect();
+		threads.iter()
+			.filter(|thread| {
+				let Some(&end) = thread.tacks.last() else {
+					return false;
+				};
+				if thread.tacks.len() < 2 {
+					return true;
+				}
+				let continues = threads.iter().any(|other| other.tacks.len() > 1 && other.tacks[0] == end);
+				let suspect = matches!(self.tiles[self.tacks[end].tile].tile_type, TileType::C(_));
+				!continues && !suspect
+			})
+			.count()
+	}
+}
+
+impl Verdict {
+	pub fn is_solved(&self) -> bool {
+		self.cases.iter().all(|case| case.solved)
+	}
+}
+
+pub fn check_solution(
+	board: &Board,
+	solution: &Solution,
+) -> Verdict {
+	let mut cases = Vec::new();
+	for (group, required_evidence) in solution.cases.iter().enumerate() {
+		let evidence = board.group_evidence(group);
+		let missing: Vec<TileType> = required_evidence.iter()
+			.filter(|required| !evidence.contains(required))
+			.copied()
+			.collect();
+		let wrong_suspect = evidence.iter()
+			.find(|tile_type| matches!(tile_type, TileType::C(_)) && !required_evidence.contains(tile_type))
+			.copied();
+		let extra: Vec<TileType> = evidence.iter()
+			.filter(|tile_type| !matches!(tile_type, TileType::V(_) | TileType::C(_)) && !required_evidence.contains(tile_type))
+			.copied()
+			.collect();
+		cases.push(CaseVerdict {
+			// Stricter than the original check, which only looked for the required tiles,
+			// so accusing a second suspect alongside the culprit no longer passes
+			solved: missing.is_empty() && wrong_suspect.is_none(),
+			missing: missing,
+			wrong_suspect: wrong_suspect,
+			extra: extra,
+			unterminated: board.unterminated_threads(group),
+		});
+	}
+	Verdict {cases: cases}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{derivables::PIN_GROUP, levels::LevelAsset};
+
+	fn shipped_level(
+		name: &str,
+	) -> LevelAsset {
+		let path = format!("{}/assets/levels/{}.level.ron", env!("CARGO_MANIFEST_DIR"), name);
+		let text = std::fs::read_to_string(&path).unwrap();
+		ron::de::from_str(&text).unwrap()
+	}
+
+	// Every tile on the board with a tack in its starting group, nothing threaded
+	fn unthreaded_board(
+		level: &LevelAsset,
+	) -> Board {
+		let mut board = Board::default();
+		for (tile, tile_def) in level.tiles.iter().enumerate() {
+			board.tiles.push(BoardTile {
+				tile_type: tile_def.tile,
+				cell: None,
+			});
+			board.tacks.push(BoardTack {
+				tile: tile,
+				group: tile_def.group(),
+			});
+		}
+		board
+	}
+
+	fn tack_of(
+		board: &Board,
+		tile_type: TileType,
+	) -> usize {
+		board.tacks.iter().position(|tack| board.tiles[tack.tile].tile_type == tile_type).unwrap()
+	}
+
+	// String tile types together in order, victim first and suspect last
+	fn thread_chain(
+		board: &mut Board,
+		group: usize,
+		chain: &[TileType],
+	) {
+		let tacks: Vec<usize> = chain.iter().map(|tile_type| tack_of(board, *tile_type)).collect();
+		for &tack in tacks.iter() {
+			board.tacks[tack].group = group;
+		}
+		for pair in tacks.windows(2) {
+			board.threads.push(BoardThread {
+				group: group,
+				tacks: pair.to_vec(),
+			});
+		}
+	}
+
+	fn case_chain(
+		case: usize,
+		solution: &[TileType],
+	) -> Vec<TileType> {
+		let mut chain = vec![TileType::V(case)];
+		chain.extend(solution.iter().filter(|tile_type| !matches!(tile_type, TileType::C(_))));
+		chain.extend(solution.iter().filter(|tile_type| matches!(tile_type, TileType::C(_))));
+		chain
+	}
+
+	// The intended solution of the level, each case threaded from its victim to its culprit
+	fn solved_board(
+		level: &LevelAsset,
+	) -> Board {
+		let mut board = unthreaded_board(level);
+		for (case, solution) in level.solution().cases.iter().enumerate() {
+			thread_chain(&mut board, case, &case_chain(case, solution));
+		}
+		board
+	}
+
+	#[test]
+	fn shipped_levels_are_solvable() {
+		for name in ["level_0", "level_1", "level_2"] {
+			let level = shipped_level(name);
+			let verdict = check_solution(&solved_board(&level), &level.solution());
+			assert!(verdict.is_solved(), "{} not solved: {:?}", name, verdict);
+			for case in verdict.cases.iter() {
+				assert!(case.extra.is_empty() && case.unterminated == 0, "{}: {:?}", name, case);
+			}
+		}
+	}
+
+	#[test]
+	fn threads_routed_through_pins_still_solve() {
+		let level = shipped_level("level_0");
+		let mut board = solved_board(&level);
+		board.tiles.push(BoardTile {
+			tile_type: TileType::P,
+			cell: None,
+		});
+		board.tacks.push(BoardTack {
+			tile: board.tiles.len() - 1,
+			group: PIN_GROUP,
+		});
+		let pin = board.tacks.len() - 1;
+		// Every thread of the first case bends around the same pin
+		for thread in board.threads.iter_mut().filter(|thread| thread.group == 0) {
+			thread.tacks.insert(1, pin);
+		}
+		let verdict = check_solution(&board, &level.solution());
+		assert!(verdict.is_solved(), "{:?}", verdict);
+		assert!(verdict.cases[0].extra.is_empty() && verdict.cases[0].unterminated == 0, "{:?}", verdict.cases[0]);
+		// Left loose on the pin, the last thread no longer reaches the suspect
+		board.threads.iter_mut().filter(|thread| thread.group == 0).last().unwrap().tacks.truncate(2);
+		assert_eq!(board.unterminated_threads(0), 1);
+	}
+
+	#[test]
+	fn empty_board_is_not_solved() {
+		let level = shipped_level("level_0");
+		let verdict = check_solution(&unthreaded_board(&level), &level.solution());
+		assert!(!verdict.is_solved());
+		assert!(verdict.cases.iter().all(|case| !case.solved));
+	}
+
+	#[test]
+	fn missing_link_fails_its_case() {
+		let level = shipped_level("level_0");
+		let mut board = unthreaded_board(&level);
+		// Case 1 skips the fingerprints
+		thread_chain(&mut board, 0, &[TileType::V(0), TileType::I, TileType::C(0)]);
+		thread_chain(&mut board, 1, &case_chain(1, &level.cases[1].solution));
+		thread_chain(&mut board, 2, &case_chain(2, &level.cases[2].solution));
+		let verdict = check_solution(&board, &level.solution());
+		assert!(!verdict.is_solved());
+		assert_eq!(verdict.cases[0].missing, vec![TileType::O]);
+		assert!(verdict.cases[1].solved && verdict.cases[2].solved);
+	}
+
+	#[test]
+	fn extra_evidence_is_reported() {
+		let level = shipped_level("level_0");
+		let mut board = solved_board(&level);
+		// Case 3's firearm threaded into case 1 as well
+		let firearm = tack_of(&board, TileType::L);
+		board.tacks[firearm].group = 0;
+		let verdict = check_solution(&board, &level.solution());
+		assert_eq!(verdict.cases[0].extra, vec![TileType::L]);
+		assert_eq!(verdict.cases[2].missing, vec![TileType::L]);
+		assert!(!verdict.is_solved());
+	}
+
+	#[test]
+	fn wrong_suspect_fails_its_case() {
+		let level = shipped_level("level_0");
+		let mut board = unthreaded_board(&level);
+		thread_chain(&mut board, 0, &[TileType::V(0), TileType::I, TileType::O, TileType::C(1)]);
+		let verdict = check_solution(&board, &level.solution());
+		assert!(!verdict.cases[0].solved);
+		assert_eq!(verdict.cases[0].missing, vec![TileType::C(0)]);
+	}
+
+	#[test]
+	fn wrong_suspect_fails_a_case_with_all_its_evidence() {
+		let level = shipped_level("level_0");
+		let mut board = solved_board(&level);
+		// A second card for case 2's culprit, accused in case 1 as well so case 2 keeps its own
+		board.tiles.push(BoardTile {
+			tile_type: TileType::C(1),
+			cell: None,
+		});
+		board.tacks.push(BoardTack {
+			tile: board.tiles.len() - 1,
+			group: 0,
+		});
+		board.threads.push(BoardThread {
+			group: 0,
+			tacks: vec![tack_of(&board, TileType::O), board.tacks.len() - 1],
+		});
+		let verdict = check_solution(&board, &level.solution());
+		assert!(verdict.cases[0].missing.is_empty());
+		assert_eq!(verdict.cases[0].wrong_suspect, Some(TileType::C(1)));
+		assert!(!verdict.cases[0].solved);
+		assert!(verdict.cases[1].solved && verdict.cases[2].solved, "{:?}", verdict);
+	}
+
+	#[test]
+	fn loose_ends_are_reported_without_failing_the_case() {
+		let level = shipped_level("level_0");
+		let mut board = solved_board(&level);
+		// A thread started from the victim and left hanging
+		let victim = tack_of(&board, TileType::V(0));
+		board.threads.push(BoardThread {
+			group: 0,
+			tacks: vec![victim],
+		});
+		let verdict = check_solution(&board, &level.solution());
+		assert_eq!(verdict.cases[0].unterminated, 1);
+		assert!(verdict.cases[0].solved);
+	}
+
+	#[test]
+	fn unterminated_threads_are_counted() {
+		let level = shipped_level("level_0");
+		let mut board = unthreaded_board(&level);
+		// Stops at the evidence without reaching a suspect
+		thread_chain(&mut board, 0, &[TileType::V(0), TileType::I, TileType::O]);
+		assert_eq!(board.unterminated_threads(0), 1);
+		let verdict = check_solution(&board, &level.solution());
+		assert_eq!(verdict.cases[0].unterminated, 1);
+		assert!(!verdict.cases[0].solved);
+	}
+}
@@ -1,10 +1,10 @@
 use std::time::Duration;
 
 // Buttons module for handling UI interaction
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{prelude::*, utils::HashMap};
 use bevy_kira_audio::{AudioInstance, PlaybackState, AudioTween, Audio, AudioControl};
 
-use crate::{derivables::*, tiles::spawn_tile};
+use crate::{board::*, derivables::*, levels::{LevelAsset, spawn_level_tiles}, threads::spawn_thread};
 
 pub struct ButtonsPlugin;
 
@@ -15,8 +15,10 @@ impl Plugin for ButtonsPlugin {
                 handle_button_interaction,
                 string_trash.before(load_level),
 				load_level,
+                mirror_board.run_if(on_event::<SolveCaseEvent>()).before(solve_case),
                 solve_case,
 				update_music,
+				update_level_text,
             ).run_if(in_state(GameState::Game)))
 		;
 	}
@@ -27,24 +29,28 @@ fn handle_button_interaction(
 	mut hint_text_query: Query<(&mut Transform, (With<HintText>, Without<UIButton>))>,
 	mut solve_text_query: Query<(&mut Transform, (With<SolveText>, Without<HintText>, Without<UIButton>))>,
     mut volume_toggle: ResMut<VolumeToggle>,
+    mut progress: ResMut<Progress>,
+    registry: Res<LevelRegistry>,
     mut ev_w_level: EventWriter<LevelSelectedEvent>,
     mut ev_w_solve: EventWriter<SolveCaseEvent>,
+    mut ev_w_undo: EventWriter<UndoEvent>,
+    mut ev_w_redo: EventWriter<RedoEvent>,
 	asset_server: Res<AssetServer>,
 	audio: Res<Audio>,
     level: Res<Level>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-	mouse: Res<Input<MouseButton>>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
 ) {
 	for (mut hintbox_pos, _) in hint_text_query.iter_mut() {
 		hintbox_pos.translation.z = -10.0;
 	}
-    let window = window_query.get_single().unwrap();
-	if let Some(cursor_pos) = window.cursor_position() {
-		let cursor_pos = cursor_to_screen(cursor_pos, window);
+	if let Some(cursor_pos) = pointer.ui {
         for (button_pos, mut button_sprite, button) in button_query.iter_mut() {
             if (button_pos.translation.x - cursor_pos.x).abs() < button.size.x / 2.0
             && (button_pos.translation.y - cursor_pos.y).abs() < button.size.y / 2.0 {
-				if button.function != 1 {
+				if button.function == 2 && !registry.next(level.0).is_some_and(|next| progress.unlocked(&registry, next)) {
+					button_sprite.color = Color::rgb(0.4, 0.4, 0.4);
+				} else if button.function != 1 {
                 	button_sprite.color = Color::rgb(1.0, 1.0, 0.4);
 				}
 				if button.function == 0 || button.function == 2 {
@@ -57,16 +63,22 @@ fn handle_button_interaction(
 						hintbox_pos.translation.z = 900.0;
 					}
 				}
-                if mouse.just_pressed(MouseButton::Left) {
+                if actions.just_pressed(Action::Select) {
                     match button.function {
-                        // Prev, Level, Next, Music, Sfx, Restart, Solve
-                        0 => ev_w_level.send(LevelSelectedEvent{level: if level.0>0{level.0-1}else{0}}),
-                        1 => {}, //ev_w_level.send(LevelSelectedEvent{level: level.0}),
-                        2 => ev_w_level.send(LevelSelectedEvent{level: if level.0<2{level.0+1}else{2}}),
+                        // Prev, Level, Next, Music, Sfx, Restart, Solve, Help, Undo, Redo, Free Play
+                        0 => if let Some(prev) = registry.prev(level.0) {
+                            ev_w_level.send(LevelSelectedEvent{level: prev, edited: false})
+                        },
+                        2 => if let Some(next) = registry.next(level.0).filter(|next| progress.unlocked(&registry, *next)) {
+                            ev_w_level.send(LevelSelectedEvent{level: next, edited: false})
+                        },
                         3 => volume_toggle.bgm = !volume_toggle.bgm,
                         4 => volume_toggle.sfx = !volume_toggle.sfx,
-                        5 => ev_w_level.send(LevelSelectedEvent{level: level.0}),
+                        5 => ev_w_level.send(LevelSelectedEvent{level: level.0, edited: false}),
                         6 => ev_w_solve.send(SolveCaseEvent()),
+                        8 => ev_w_undo.send(UndoEvent()),
+                        9 => ev_w_redo.send(RedoEvent()),
+                        10 => progress.free_play = !progress.free_play,
                         _ => {},
                     }
 					if volume_toggle.sfx && button.function != 1 && button.function != 7 {
@@ -77,6 +89,8 @@ fn handle_button_interaction(
 				if (button.function == 3 && !volume_toggle.bgm)
 				|| (button.function == 4 && !volume_toggle.sfx) {
 					button_sprite.color = Color::rgb(1.0, 0.4, 0.4)
+				} else if button.function == 2 && !registry.next(level.0).is_some_and(|next| progress.unlocked(&registry, next)) {
+					button_sprite.color = Color::rgb(0.4, 0.4, 0.4)
 				} else {
 					button_sprite.color = Color::rgb(1.0, 1.0, 1.0);
 				};
@@ -86,74 +100,55 @@ fn handle_button_interaction(
 
 }
 
-struct TileInfo {
-	location: Vec3,
-	tile_type: TileType,
-	group: usize,
-	origin: bool,
-	tack_tile: usize,
-	links: i32,
-	suspect: bool,
-}
-
-fn load_level(
+pub fn load_level(
 	mut commands: Commands,
 	mut level: ResMut<Level>,
+	mut pending_level: Local<Option<(usize, bool)>>,
+	mut loaded: Local<bool>,
 	mut ev_r_level: EventReader<LevelSelectedEvent>,
 	mut ev_w_wait: EventWriter<WaitForJunkEvent>,
+	mut ev_w_action: EventWriter<BoardActionEvent>,
+	mut thread_count: ResMut<ThreadCount>,
+	mut threads_block_tiles: ResMut<ThreadsBlockTiles>,
+	mut board: ResMut<BoardConfig>,
 	asset_server: Res<AssetServer>,
-	// keyboard: Res<Input<KeyCode>>,
+	registry: Res<LevelRegistry>,
+	level_assets: Res<Assets<LevelAsset>>,
 	remove_on_reset: Query<(Entity, &RemoveOnReset)>,
 	mut level_text_query: Query<(&mut Text, With<LevelText>)>,
-	mut case_files_text_query: Query<(&mut Text, &CaseFileText, Without<LevelText>)>,
 ) {
-	// let mut next_level = 999;
-	// for key in keyboard.get_just_pressed() {
-	// 	match key {
-	// 		KeyCode::Key0 => next_level = 0,
-	// 		KeyCode::Key1 => next_level = 1,
-	// 		KeyCode::Key2 => next_level = 2,
-	// 		KeyCode::Key3 => next_level = 3,
-	// 		_ => (),
-	// 	}
-	// }
 	for ev in ev_r_level.read() {
-		level.0 = ev.level;
-		for (entity, _) in remove_on_reset.iter() {
-			commands.entity(entity).despawn_recursive();
-		}
+		*pending_level = Some((ev.level, ev.edited));
+	}
+	let Some((next_level, edited)) = *pending_level else {
+		return;
+	};
+	// Level files load in the background, so hold onto the request until it's ready
+	let Some(level_asset) = registry.handle(next_level).and_then(|handle| level_assets.get(handle)) else {
+		return;
+	};
+	*pending_level = None;
+	let restart = *loaded && level.0 == next_level && !edited;
+	*loaded = true;
+	level.0 = next_level;
+	thread_count.0 = vec![0.0; level_asset.cases.len()];
+	threads_block_tiles.0 = level_asset.threads_block_tiles;
+	if *board != level_asset.board {
+		*board = level_asset.board;
+	}
 
-		let mut tile_infos = Vec::new();
-		
-		for i in 0..get_level_layout(level.0).len() {
-			tile_infos.push(TileInfo {
-				location: get_level_layout(level.0)[i],
-				tile_type: get_tile_types(level.0)[i],
-				group: get_tile_group(level.0)[i],
-				origin: match get_tile_types(level.0)[i]{TileType::V(_) => true, _=> false},
-				tack_tile: get_tack_tilette(level.0)[i],
-				links: get_junk_links(level.0)[i],
-				suspect: match get_tile_types(level.0)[i]{TileType::C(_) => true, _=> false},
-			});
-		}
-		for tile_info in tile_infos.iter() {
-			let tile = spawn_tile(&mut commands, tile_info.location, tile_info.tile_type, &asset_server,
-				tile_info.group, tile_info.origin, tile_info.links, tile_info.suspect, tile_info.tack_tile);
-			match tile_info.tile_type {
-				TileType::C(_) | TileType::V(_) | TileType::W(_) => {commands.entity(tile).insert(Immovable);},
-				_ => (),
-			}
-		}
+	for (entity, _) in remove_on_reset.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
 
-		for (mut text, _) in level_text_query.iter_mut() {
-			text.sections[0].value = format!("{}", ev.level + 1);
-		}
-		for (mut text, case, _) in case_files_text_query.iter_mut() {
-			text.sections[1].value = get_case_report(ev.level, case.0);
-		}
+	spawn_level_tiles(&mut commands, &asset_server, level_asset);
 
-		ev_w_wait.send(WaitForJunkEvent());
+	for (mut text, _) in level_text_query.iter_mut() {
+		text.sections[0].value = format!("{}", level.0 + 1);
 	}
+
+	ev_w_wait.send(WaitForJunkEvent());
+	ev_w_action.send(BoardActionEvent(if restart {BoardAction::Restart} else {BoardAction::Load}));
 }
 
 
@@ -172,63 +167,66 @@ fn string_trash(
 			let tacks = [tack_a_entity, tack_b_entity].to_vec();
 			let tacks_locs = [tack_a_pos.translation().xy(), tack_b_pos.translation().xy()].to_vec();
 			if (tack_a.links-tack_b.links).abs() == 1 {
-				let thread_entity = commands.spawn((
-					SpriteBundle {
-						transform: Transform::from_xyz(
-							(tack_a_pos.translation().x + tack_b_pos.translation().x)/2.0,
-							(tack_a_pos.translation().y + tack_b_pos.translation().y)/2.0,
-							500.0,
-						),
-						sprite: Sprite {
-							custom_size: Some(Vec2::new(6.0, 1.0)),
-							color: get_tack_color(4),
-							..default()
-						},
-						..default()
-					},
-					Thread {
-						group: 4,
-						index: -1.0,
-						tacks: tacks,
-						tacks_locs: tacks_locs,
-					},
-					RemoveOnReset,
-				)).id();
+				spawn_thread(&mut commands, JUNK_GROUP, -1.0, tacks, tacks_locs);
 			}
 		}
 	}
 }
 
+// Copy tiles, tacks and threads into the plain board model, only needed when the case is checked
+fn mirror_board(
+	mut board_model: ResMut<BoardModel>,
+	tile_query: Query<(&Transform, &Tile, &Children)>,
+	tack_query: Query<(Entity, &Tack)>,
+	thread_query: Query<&Thread>,
+	board_config: Res<BoardConfig>,
+) {
+	let mut board = Board::default();
+	let mut tack_indices = HashMap::new();
+	for (tile_pos, tile, children) in tile_query.iter() {
+		let mut cell = None;
+		if board_config.contains(tile_pos.translation.xy()) {
+			cell = Some(board_config.grid_to_index([tile_pos.translation].to_vec())[0]);
+		}
+		board.tiles.push(BoardTile {
+			tile_type: tile.0,
+			cell: cell,
+		});
+		for &child in children.iter() {
+			if let Ok((tack_entity, tack)) = tack_query.get(child) {
+				tack_indices.insert(tack_entity, board.tacks.len());
+				board.tacks.push(BoardTack {
+					tile: board.tiles.len() - 1,
+					group: tack.group,
+				});
+			}
+		}
+	}
+	for thread in thread_query.iter() {
+		board.threads.push(BoardThread {
+			group: thread.group,
+			tacks: thread.tacks.iter().filter_map(|tack| tack_indices.get(tack).copied()).collect(),
+		});
+	}
+	board_model.0 = board;
+}
+
 fn solve_case(
 	mut solve_text_query: Query<(&mut Transform, With<SolveText>)>,
 	mut fail_text_query: Query<(&mut Transform, (With<FailText>, Without<SolveText>))>,
 	mut ev_r_solve: EventReader<SolveCaseEvent>,
-	tack_query: Query<&Tack>,
+	mut ev_w_result: EventWriter<SolveResultEvent>,
+	board_model: Res<BoardModel>,
 	level: Res<Level>,
+	registry: Res<LevelRegistry>,
+	level_assets: Res<Assets<LevelAsset>>,
 ) {
 	for _ in ev_r_solve.read() {
-		let mut evidence =[Vec::new(), Vec::new(), Vec::new()];
-		let required_evidence = [
-			[[TileType::C(0), TileType::I, TileType::O].to_vec(), [TileType::C(1), TileType::J].to_vec(), [TileType::C(2), TileType::L].to_vec()],
-			[[TileType::C(0), TileType::L, TileType::S].to_vec(), [TileType::C(1), TileType::T].to_vec(), [TileType::C(2), TileType::Z].to_vec()],
-			[[TileType::C(0), TileType::L, TileType::J].to_vec(), [TileType::C(1), TileType::B, TileType::O].to_vec(), [TileType::C(2), TileType::T].to_vec()],
-		];
-
-		for tack in tack_query.iter() {
-			if tack.group < 3 {
-				evidence[tack.group].push(tack.tile_type);
-			}
-		}
-		let mut solved = [true, true, true];
-		for case in 0..3{
-			for required in &required_evidence[level.0][case] {
-				if !evidence[case].contains(required) {
-					solved[case] = false;
-					continue;
-				}
-			}
-		}
-		if solved[0] && solved[1] && solved[2] {
+		let Some(level_asset) = registry.handle(level.0).and_then(|handle| level_assets.get(handle)) else {
+			continue;
+		};
+		let verdict = check_solution(&board_model.0, &level_asset.solution());
+		if verdict.is_solved() {
 			for (mut solve_pos, _) in solve_text_query.iter_mut() {
 				solve_pos.translation.z = 990.0;
 			}
@@ -236,11 +234,8 @@ fn solve_case(
 			for (mut fail_pos, _) in fail_text_query.iter_mut() {
 				fail_pos.translation.z = 980.0;
 			}
-			// println!("Hmm, this evidence doesn't seem to add up...");
-			// if !solved[0] {println!("Case 1 doesn't seem to have enough evidence connecting the suspect to the victim...")};
-			// if !solved[1] {println!("Case 2 doesn't seem to have enough evidence connecting the suspect to the victim...")};
-			// if !solved[2] {println!("Case 3 doesn't seem to have enough evidence connecting the suspect to the victim...")};
 		}
+		ev_w_result.send(SolveResultEvent{verdict: verdict});
 	}
 }
 
@@ -264,4 +259,24 @@ fn update_music(
 			_ => {}
 		}
 	}
-}
\ No newline at end of file
+}
+
+// Show whether the current level has been solved and whether free play is on
+fn update_level_text(
+	mut level_text_query: Query<(&mut Text, (With<LevelText>, Without<FreePlayText>))>,
+	mut free_play_text_query: Query<(&mut Text, With<FreePlayText>)>,
+	progress: Res<Progress>,
+	registry: Res<LevelRegistry>,
+	level: Res<Level>,
+) {
+	if !progress.is_changed() && !level.is_changed() {return;};
+	let solved = registry.name(level.0).is_some_and(|name| progress.solved(name));
+	for (mut text, _) in level_text_query.iter_mut() {
+		text.sections[0].style.color = if progress.free_play {Color::rgb(0.7, 0.9, 1.0)} else {Color::rgb(0.9, 0.9, 0.9)};
+		text.sections[1].value = if solved {"\nSolved".to_string()} else {String::new()};
+	}
+	for (mut text, _) in free_play_text_query.iter_mut() {
+		text.sections[1].value = if progress.free_play {"On".to_string()} else {"Off".to_string()};
+		text.sections[1].style.color = if progress.free_play {Color::rgb(0.7, 0.9, 1.0)} else {Color::rgb(0.9, 0.9, 0.9)};
+	}
+}
@@ -0,0 +1,213 @@
+// Camera module for zooming in on and panning around the evidence board
+use bevy::{prelude::*, input::touch::Touches, render::camera::Viewport, window::PrimaryWindow};
+
+use crate::derivables::*;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_systems(Update, (
+				fit_board_viewport,
+				fit_board_zoom,
+			))
+			.add_systems(Update, (
+				pinch_zoom,
+				wheel_zoom,
+				pan_camera,
+			).run_if(in_state(GameState::Game)))
+			// The editor panel is laid out for the whole view
+			.add_systems(OnEnter(GameState::Editor), (
+				reset_zoom,
+			))
+		;
+	}
+}
+
+// Fraction of the board view shown when zoomed all the way in
+const MIN_ZOOM: f32 = 0.4;
+// Zoom for each notch of the wheel
+const ZOOM_STEP: f32 = 1.15;
+
+// The board camera only draws over the board painted on the background, so the buttons,
+// workbench and case files stay put in the fixed view around it
+fn fit_board_viewport(
+	mut camera_query: Query<&mut Camera, With<BoardCamera>>,
+	window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+	let Ok(window) = window_query.get_single() else {return};
+	let Ok(mut camera) = camera_query.get_single_mut() else {return};
+	let window_size = Vec2::new(window.physical_width() as f32, window.physical_height() as f32);
+	if window_size.min_element() < 1.0 {return;};
+	// The fixed view stretches VIEW_SIZE over the window, measured from the top left
+	let corner = Vec2::new(BOARD_VIEW_CENTRE.x - BOARD_VIEW_SIZE.x/2.0, -BOARD_VIEW_CENTRE.y - BOARD_VIEW_SIZE.y/2.0) + VIEW_SIZE/2.0;
+	let position = (corner / VIEW_SIZE * window_size).round();
+	let size = (BOARD_VIEW_SIZE / VIEW_SIZE * window_size).round().max(Vec2::ONE);
+	let (physical_position, physical_size) = (position.as_uvec2(), size.as_uvec2());
+	if camera.viewport.as_ref().is_some_and(|viewport| viewport.physical_position == physical_position && viewport.physical_size == physical_size) {return;};
+	camera.viewport = Some(Viewport {
+		physical_position: physical_position,
+		physical_size: physical_size,
+		..default()
+	});
+}
+
+// The board painted on the background, stretched to take in any of a bigger board that reaches past it
+fn board_bounds(
+	board: &BoardConfig,
+) -> Rect {
+	Rect::from_center_size(BOARD_VIEW_CENTRE, BOARD_VIEW_SIZE).union(Rect::from_center_size(board.origin, board.size()))
+}
+
+// Zoomed all the way out the whole board fits in the board view
+fn max_zoom(
+	board: &BoardConfig,
+) -> f32 {
+	(board_bounds(board).size() / BOARD_VIEW_SIZE).max_element()
+}
+
+// Zoom while keeping the anchor at the same spot on screen
+fn zoom_about(
+	camera_pos: &mut Transform,
+	projection: &mut OrthographicProjection,
+	board: &BoardConfig,
+	anchor: Vec2,
+	scale: f32,
+) {
+	let scale = scale.clamp(MIN_ZOOM, max_zoom(board));
+	let offset = (anchor - camera_pos.translation.xy()) * (1.0 - scale / projection.scale);
+	projection.scale = scale;
+	camera_pos.translation += offset.extend(0.0);
+	keep_in_bounds(camera_pos, board, scale);
+}
+
+// The view can't wander off the board
+fn keep_in_bounds(
+	camera_pos: &mut Transform,
+	board: &BoardConfig,
+	scale: f32,
+) {
+	let bounds = board_bounds(board);
+	let slack = (bounds.half_size() - BOARD_VIEW_SIZE/2.0 * scale).max(Vec2::ZERO);
+	let centre = camera_pos.translation.xy().clamp(bounds.center() - slack, bounds.center() + slack);
+	camera_pos.translation = centre.extend(camera_pos.translation.z);
+}
+
+// Two fingers spreading apart zoom in around the point between them
+fn pinch_zoom(
+	mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+	pointer: Res<Pointer>,
+	touches: Res<Touches>,
+	case_report_stack: Res<CaseReportStack>,
+	board: Res<BoardConfig>,
+) {
+	let fingers: Vec<_> = touches.iter().collect();
+	let [first, second] = fingers[..] else {return};
+	let before = first.previous_position().distance(second.previous_position());
+	let after = first.position().distance(second.position());
+	if before <= 0.0 || after <= 0.0 {return;};
+	// The pointer sits between the fingers
+	let Some(anchor) = pointer.pos.filter(|pos| board.contains(*pos) && !case_report_stack.hovered) else {return};
+	let Ok((mut camera_pos, mut projection)) = camera_query.get_single_mut() else {return};
+	let scale = projection.scale * before / after;
+	zoom_about(&mut camera_pos, &mut projection, &board, anchor, scale);
+}
+
+fn wheel_zoom(
+	mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+	held_query: Query<(), With<Held>>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
+	case_report_stack: Res<CaseReportStack>,
+	board: Res<BoardConfig>,
+) {
+	// The wheel rotates held tiles instead
+	if !held_query.is_empty() {return;};
+	let steps = actions.just_pressed(Action::ZoomOut) as i32 - actions.just_pressed(Action::ZoomIn) as i32;
+	if steps == 0 {return;};
+	let Some(anchor) = pointer.pos.filter(|pos| board.contains(*pos) && !case_report_stack.hovered) else {return};
+	let Ok((mut camera_pos, mut projection)) = camera_query.get_single_mut() else {return};
+	let scale = projection.scale * ZOOM_STEP.powi(steps);
+	zoom_about(&mut camera_pos, &mut projection, &board, anchor, scale);
+}
+
+// Keeps the board point that was grabbed under the cursor
+fn pan_camera(
+	mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<BoardCamera>>,
+	mut grabbed: Local<Option<Vec2>>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
+	case_report_stack: Res<CaseReportStack>,
+	board: Res<BoardConfig>,
+) {
+	if actions.just_pressed(Action::Pan) {
+		*grabbed = pointer.pos.filter(|pos| board.contains(*pos) && !case_report_stack.hovered);
+	}
+	if !actions.pressed(Action::Pan) {
+		*grabbed = None;
+	}
+	let (Some(grabbed), Some(cursor_pos)) = (*grabbed, pointer.pos) else {return};
+	if grabbed == cursor_pos {return;};
+	let Ok((mut camera_pos, projection)) = camera_query.get_single_mut() else {return};
+	camera_pos.translation += (grabbed - cursor_pos).extend(0.0);
+	keep_in_bounds(&mut camera_pos, &board, projection.scale);
+}
+
+// Zoomed out just far enough to take in the whole board
+fn fitted_view(
+	board: &BoardConfig,
+) -> BoardView {
+	BoardView {
+		centre: board_bounds(board).center(),
+		scale: max_zoom(board),
+	}
+}
+
+// Each level starts out showing all of its board
+fn fit_board_zoom(
+	mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+	mut ev_r_action: EventReader<BoardActionEvent>,
+	board: Res<BoardConfig>,
+) {
+	let loaded = ev_r_action.read().any(|ev| ev.0 == BoardAction::Load);
+	if !loaded {return;};
+	let view = fitted_view(&board);
+	for (mut camera_pos, mut projection) in camera_query.iter_mut() {
+		projection.scale = view.scale;
+		camera_pos.translation = view.centre.extend(camera_pos.translation.z);
+	}
+}
+
+fn reset_zoom(
+	mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+) {
+	for (mut camera_pos, mut projection) in camera_query.iter_mut() {
+		projection.scale = 1.0;
+		camera_pos.translation = BOARD_VIEW_CENTRE.extend(camera_pos.translation.z);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn levels_start_showing_their_whole_board() {
+		for (width, height, origin) in [
+			(29, 12, BOARD_VIEW_CENTRE),
+			(10, 5, Vec2::new(-300.0, 100.0)),
+			(40, 12, BOARD_VIEW_CENTRE),
+			(29, 30, Vec2::new(0.0, 300.0)),
+			(60, 25, Vec2::new(-500.0, -200.0)),
+		] {
+			let board = BoardConfig {width: width, height: height, origin: origin};
+			let view = fitted_view(&board);
+			let shown = view.visible();
+			let board_rect = Rect::from_center_size(board.origin, board.size());
+			assert!(shown.union(board_rect) == shown, "{:?} only shows {:?}", board, shown);
+			// Boards that fit the painted one aren't zoomed out at all
+			assert_eq!(view.scale == 1.0, shown == BoardView::frame(), "{:?}", board);
+		}
+	}
+}
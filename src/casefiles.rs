@@ -1,54 +1,93 @@
 // Casefiles module for interaction and displaying of casefiles
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::prelude::*;
 use bevy_kira_audio::{Audio, AudioControl};
 
-use crate::derivables::*;
+use crate::{buttons::load_level, derivables::*, keywords::report_sections, levels::LevelAsset};
 
 pub struct CasefilesPlugin;
 
 impl Plugin for CasefilesPlugin {
 	fn build(&self, app: &mut App) {
 		app
-			.add_systems(OnEnter(GameState::Game), (
-				spawn_casefiles,
-			))
+			.init_resource::<CaseReportStack>()
 			.add_systems(Update, (
+				spawn_casefiles.after(load_level),
 				mouse_hover,
+				drag_case_files.after(mouse_hover),
+				show_verdict,
 			).run_if(in_state(GameState::Game)))
 		;
 	}
 }
 
+// Where a report opens while its folder is hovered
+const OPEN_REPORT_POS: Vec2 = Vec2::new(-200.0, 50.0);
+const OPEN_REPORT_SIZE: Vec2 = Vec2::new(536.0, 608.0);
+// Pinned reports stack above the board but under a hovered one
+const PINNED_Z: f32 = 700.0;
+// Dropping a report this close to the side of the screen docks it there
+const DOCK_MARGIN: f32 = 40.0;
+// How much of a docked report pokes out from the side
+const DOCK_TAB: f32 = 60.0;
+// Cursor travel that turns a click on a report into a drag
+const DRAG_THRESHOLD: f32 = 4.0;
+
+// One case file per case, respawned with the rest of the level
 fn spawn_casefiles(
 	mut commands: Commands,
+	mut ev_r_action: EventReader<BoardActionEvent>,
 	asset_server: Res<AssetServer>,
+	registry: Res<LevelRegistry>,
+	level_assets: Res<Assets<LevelAsset>>,
+	level: Res<Level>,
 ) {
-	for i in 0..3 {
-		let case = format!("sprites/casefiles/casefile_{}.png", i);
-		let opened_case = format!("sprites/casefiles/open_casefile_{}.png", i);
-		let case_x = i as f32 * 180.0 + 360.0;
+	let spawned = ev_r_action.read().any(|ev| ev.0 == BoardAction::Load || ev.0 == BoardAction::Restart);
+	if !spawned {return;};
+	let Some(level_asset) = registry.handle(level.0).and_then(|handle| level_assets.get(handle)) else {return};
+
+	// Fan the files out over the space three take up side by side
+	let count = level_asset.cases.len();
+	let spacing = if count > 1 {(360.0 / (count - 1) as f32).min(180.0)} else {0.0};
+	let half_width = if count > 3 {spacing / 2.0} else {82.5};
+	for (i, case_def) in level_asset.cases.iter().enumerate() {
+		let case = format!("sprites/casefiles/casefile_{}.png", i % 3);
+		let opened_case = format!("sprites/casefiles/open_casefile_{}.png", i % 3);
+		let case_x = i as f32 * spacing + 360.0;
 		commands.spawn((SpriteBundle{
 			transform: Transform::from_xyz(case_x, -240.0, 50.0),
 			texture: asset_server.load(case),
 			sprite: Sprite {
 				custom_size: Some(Vec2::new(220.0, 220.0)), // 220 x 165 collider
+				color: get_case_tint(i),
 				..default()
 			},
 			..default()
 			},
-			CaseReport {number: i, sfx: false},
+			CaseReport {number: i, half_width: half_width, sfx: false},
+			RemoveOnReset,
 		));
 		commands.spawn((SpriteBundle{
-			transform: Transform::from_xyz(-200.0, 50.0, -1.0),
+			transform: Transform::from_translation(OPEN_REPORT_POS.extend(-1.0)),
 			texture: asset_server.load(opened_case),
 			sprite: Sprite {
-				custom_size: Some(Vec2::new(536.0, 608.0)),
+				custom_size: Some(OPEN_REPORT_SIZE),
 				..default()
 			},
 			..default()
 			},
 			OpenCaseReport {number: i},
+			RemoveOnReset,
+			OVERLAY,
 		)).with_children(|parent| {
+			let (report, keywords) = report_sections(&asset_server, &case_def.report);
+			let mut sections = [TextSection::new(
+				format!("      Case {}\n", i+1),
+				TextStyle {
+					font: asset_server.load("fonts/XTypewriterBold.ttf"),
+					font_size: 32.0,
+					color: Color::rgb(0.1, 0.1, 0.1),
+				})].to_vec();
+			sections.extend(report);
 			parent.spawn((Text2dBundle{
 				text_2d_bounds: bevy::text::Text2dBounds{ size: Vec2::new(
 					350.0 - 5.0 * 2.0,
@@ -56,50 +95,51 @@ fn spawn_casefiles(
 				)},
 				transform: Transform::from_xyz(50.0 - 350.0/2.0, -90.0 + 300.0/2.0, 0.5),
 				text_anchor: bevy::sprite::Anchor::TopLeft,
-				text: Text::from_sections([
-					TextSection::new(
-					format!("      Case {}\n", i+1),
-					TextStyle {
-						font: asset_server.load("fonts/XTypewriterBold.ttf"),
-						font_size: 32.0,
-						color: Color::rgb(0.1, 0.1, 0.1),
-					}),
-					TextSection::new(
-					get_case_report(0, i),
-					TextStyle {
-						font: asset_server.load("fonts/XTypewriterBold.ttf"),
-						font_size: 28.0,
-						color: Color::rgb(0.1, 0.1, 0.1),
-					}),
-				]).with_alignment(TextAlignment::Left),
+				text: Text::from_sections(sections).with_alignment(TextAlignment::Left),
 				..default()
 				},
-				CaseFileText(i),
+				// The title isn't tagged
+				CaseFileText {keywords: [None].into_iter().chain(keywords).collect()},
+				OVERLAY,
 			));
 		});
 	}
 }
 
 fn mouse_hover(
+	mut commands: Commands,
+	mut case_report_stack: ResMut<CaseReportStack>,
 	mut case_report_query: Query<(&mut Transform, &mut CaseReport)>,
-	mut open_case_report_query: Query<(&mut Transform, &OpenCaseReport, Without<CaseReport>)>,
+	mut open_case_report_query: Query<(Entity, &mut Transform, &OpenCaseReport, Has<PinnedCaseReport>, Without<CaseReport>)>,
 	mut fail_text_query: Query<(&mut Transform, (With<FailText>, Without<OpenCaseReport>, Without<CaseReport>))>,
 	asset_server: Res<AssetServer>,
 	audio: Res<Audio>,
-	window_query: Query<&Window, With<PrimaryWindow>>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
 	volume_toggle: Res<VolumeToggle>,
 ) {
-	let window = window_query.get_single().unwrap();
-
 	for (mut report_transform, mut case_report) in case_report_query.iter_mut() {
-		if let Some(cursor_pos) = window.cursor_position() {
-			let cursor_pos = cursor_to_screen(cursor_pos, window);
-			if ((report_transform.translation.x - 27.5) - cursor_pos.x).abs() <= 82.5 && (report_transform.translation.y - cursor_pos.y).abs() <= 110.0 {
+		if let Some(cursor_pos) = pointer.ui {
+			if ((report_transform.translation.x - 27.5) - cursor_pos.x).abs() <= case_report.half_width && (report_transform.translation.y - cursor_pos.y).abs() <= 110.0 {
 				for (mut fail_pos, _) in fail_text_query.iter_mut() {
 					fail_pos.translation.z = -10.0;
 				}
-				for (mut open_transform, open_case_report, _) in open_case_report_query.iter_mut(){
+				for (open_entity, mut open_transform, open_case_report, pinned, _) in open_case_report_query.iter_mut(){
 					if case_report.number == open_case_report.number {
+						// Clicking the folder pins its report open, clicking again puts it away
+						if actions.just_pressed(Action::Select) {
+							if pinned {
+								commands.entity(open_entity).remove::<PinnedCaseReport>();
+								case_report_stack.order.retain(|&entity| entity != open_entity);
+								open_transform.translation = OPEN_REPORT_POS.extend(open_transform.translation.z);
+							} else {
+								commands.entity(open_entity).insert(PinnedCaseReport {home: OPEN_REPORT_POS, docked: false});
+								case_report_stack.order.push(open_entity);
+								continue;
+							}
+						} else if pinned {
+							continue;
+						}
 						if case_report.sfx == false {
 							if volume_toggle.sfx{
 								audio.play(asset_server.load("sounds/rustle.ogg")).with_volume(SFX_VOLUME);
@@ -111,14 +151,131 @@ fn mouse_hover(
 					}
 				}
 			} else {
-				for (mut open_transform, open_case_report, _) in open_case_report_query.iter_mut(){
+				for (_, mut open_transform, open_case_report, pinned, _) in open_case_report_query.iter_mut(){
 					if case_report.number == open_case_report.number {
 						case_report.sfx = false;
 						report_transform.translation.z = 50.0;
-						open_transform.translation.z = -1.0;
+						if !pinned {
+							open_transform.translation.z = -1.0;
+						}
 					}
 				}
 			}
 		};
 	}
-}
\ No newline at end of file
+}
+
+// Pinned reports can be dragged about, brought to the front, or docked at the side of the screen
+pub fn drag_case_files(
+	mut case_report_stack: ResMut<CaseReportStack>,
+	mut pinned_query: Query<(&mut Transform, &mut PinnedCaseReport)>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
+	// Report being dragged, its offset from the cursor and where the press started
+	mut dragging: Local<Option<(Entity, Vec2, Vec2)>>,
+) {
+	// Reports despawned with the level drop out of the stack
+	case_report_stack.order.retain(|&entity| pinned_query.contains(entity));
+	let cursor_pos = pointer.ui;
+
+	let under_cursor = cursor_pos.and_then(|cursor_pos| case_report_stack.order.iter().rev().copied().find(|&entity| {
+		pinned_query.get(entity).is_ok_and(|(report_pos, _)| {
+			(cursor_pos - report_pos.translation.xy()).abs().cmple(OPEN_REPORT_SIZE/2.0).all()
+		})
+	}));
+	let hovered = under_cursor.is_some() || dragging.is_some();
+	if case_report_stack.hovered != hovered {
+		case_report_stack.hovered = hovered;
+	}
+
+	if let (Some(cursor_pos), Some(entity)) = (cursor_pos, under_cursor) {
+		if actions.just_pressed(Action::Select) {
+			// Clicked reports come to the front
+			case_report_stack.order.retain(|&other| other != entity);
+			case_report_stack.order.push(entity);
+			let (report_pos, _) = pinned_query.get(entity).unwrap();
+			*dragging = Some((entity, report_pos.translation.xy() - cursor_pos, cursor_pos));
+		}
+	}
+
+	if let (Some(cursor_pos), Some((entity, offset, pressed_at))) = (cursor_pos, *dragging) {
+		if let Ok((mut report_pos, mut pinned)) = pinned_query.get_mut(entity) {
+			let moved = cursor_pos.distance(pressed_at) > DRAG_THRESHOLD;
+			if actions.pressed(Action::Select) && moved {
+				report_pos.translation = (cursor_pos + offset).extend(report_pos.translation.z);
+			}
+			if actions.just_released(Action::Select) {
+				let edge = VIEW_SIZE.x/2.0 - DOCK_MARGIN;
+				if !moved {
+					// Clicking a docked report brings it back out
+					if pinned.docked {
+						pinned.docked = false;
+						report_pos.translation = pinned.home.extend(report_pos.translation.z);
+					}
+				} else if cursor_pos.x.abs() > edge {
+					pinned.docked = true;
+					let docked_x = VIEW_SIZE.x/2.0 + OPEN_REPORT_SIZE.x/2.0 - DOCK_TAB;
+					report_pos.translation.x = docked_x.copysign(cursor_pos.x);
+				} else {
+					pinned.docked = false;
+					pinned.home = report_pos.translation.xy();
+				}
+			}
+		}
+	}
+	if !actions.pressed(Action::Select) {
+		*dragging = None;
+	}
+
+	for (i, &entity) in case_report_stack.order.iter().enumerate() {
+		let Ok((mut report_pos, _)) = pinned_query.get_mut(entity) else {continue};
+		let z = PINNED_Z + i as f32 * 10.0;
+		if report_pos.translation.z != z {
+			report_pos.translation.z = z;
+		}
+	}
+}
+
+// Mark which case files failed and explain why in the mistrial text
+fn show_verdict(
+	mut case_report_query: Query<(&mut Sprite, &CaseReport)>,
+	mut verdict_text_query: Query<(&mut Text, With<VerdictText>)>,
+	mut ev_r_result: EventReader<SolveResultEvent>,
+	mut ev_r_level: EventReader<LevelSelectedEvent>,
+) {
+	for _ in ev_r_level.read() {
+		for (mut sprite, case_report) in case_report_query.iter_mut() {
+			sprite.color = get_case_tint(case_report.number);
+		}
+	}
+	for ev in ev_r_result.read() {
+		for (mut sprite, case_report) in case_report_query.iter_mut() {
+			if let Some(case_verdict) = ev.verdict.cases.get(case_report.number) {
+				sprite.color = if case_verdict.solved {Color::rgb(0.7, 1.0, 0.7)} else {Color::rgb(1.0, 0.5, 0.5)};
+			}
+		}
+		if ev.verdict.is_solved() {continue;};
+
+		let mut explanation = "\nThe evidence doesn't add up! Check the case files marked in red and try again.\n".to_string();
+		for (case, case_verdict) in ev.verdict.cases.iter().enumerate() {
+			if case_verdict.solved {continue;};
+			let mut problems = Vec::new();
+			if case_verdict.wrong_suspect.is_some() {
+				problems.push("wrong suspect".to_string());
+			}
+			if !case_verdict.missing.is_empty() {
+				problems.push(format!("{} missing link(s)", case_verdict.missing.len()));
+			}
+			if !case_verdict.extra.is_empty() {
+				problems.push(format!("{} unrelated item(s)", case_verdict.extra.len()));
+			}
+			if case_verdict.unterminated > 0 {
+				problems.push("loose thread".to_string());
+			}
+			explanation.push_str(&format!("\nCase {}: {}", case + 1, problems.join(", ")));
+		}
+		for (mut text, _) in verdict_text_query.iter_mut() {
+			text.sections[1].value = explanation.clone();
+		}
+	}
+}
@@ -0,0 +1,387 @@
+// Controls module for loading, saving and rebinding the action bindings
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, io::Reader}, input::mouse::MouseWheel, utils::BoxedFuture};
+use bevy_pkv::PkvStore;
+use serde::{Deserialize, Serialize};
+
+use crate::{actions::*, derivables::*, levels::{LevelLoaderError, read_ron}, saves::{load_saved, store_saved}};
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_asset::<BindingsAsset>()
+			.init_asset_loader::<BindingsLoader>()
+			.init_resource::<BindingsConfig>()
+			.init_resource::<ControlsScreen>()
+			.add_systems(Startup, (
+				load_bindings,
+			))
+			.add_systems(Update, (
+				reload_bindings,
+			))
+			.add_systems(Update, (
+				toggle_controls,
+			).run_if(in_state(GameState::Game).or_else(in_state(GameState::Controls))))
+			.add_systems(OnEnter(GameState::Controls), (
+				spawn_controls_screen,
+			))
+			.add_systems(OnExit(GameState::Controls), (
+				despawn_controls_screen,
+			))
+			.add_systems(Update, (
+				capture_binding,
+				navigate_controls,
+				update_controls_screen,
+			).chain().after(toggle_controls).run_if(in_state(GameState::Controls)))
+		;
+	}
+}
+
+// Overrides for the default bindings, loaded from assets/controls.bindings.ron
+#[derive(Asset, TypePath, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct BindingsAsset {
+	pub bindings: BindingMap,
+}
+
+#[derive(Default)]
+pub struct BindingsLoader;
+
+impl AssetLoader for BindingsLoader {
+	type Asset = BindingsAsset;
+	type Settings = ();
+	type Error = LevelLoaderError;
+
+	fn load<'a>(
+		&'a self,
+		reader: &'a mut Reader,
+		_settings: &'a (),
+		_load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<BindingsAsset, LevelLoaderError>> {
+		Box::pin(async move {
+			read_ron(reader).await
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["bindings.ron"]
+	}
+}
+
+// Defaults are overridden by the bindings file, which is overridden by rebinds from the controls screen
+#[derive(Resource, Default)]
+struct BindingsConfig {
+	file: Handle<BindingsAsset>,
+	saved: BindingMap,
+}
+
+#[derive(Resource, Default)]
+struct ControlsScreen {
+	selected: usize,
+	// Waiting for an input to bind, true if it replaces the action's bindings instead of adding to them
+	capturing: Option<bool>,
+	status: String,
+}
+
+#[derive(Component)]
+struct ControlsOnly;
+
+// Name and bindings text for a row of the controls screen, the row after the last action resets everything
+#[derive(Component)]
+struct ControlsRow(usize);
+
+#[derive(Component)]
+struct ControlsBindingsText(usize);
+
+#[derive(Component)]
+struct ControlsStatusText;
+
+const RESET_ROW: usize = ACTIONS.len();
+const ROW_TOP: f32 = 310.0;
+const ROW_HEIGHT: f32 = 32.0;
+const PANEL_SIZE: Vec2 = Vec2::new(1000.0, 860.0);
+const SELECTED_COLOR: Color = Color::rgb(1.0, 1.0, 0.4);
+const CONFLICT_COLOR: Color = Color::rgb(1.0, 0.4, 0.4);
+
+fn build_bindings(
+	config: &BindingsConfig,
+	bindings_assets: &Assets<BindingsAsset>,
+) -> ActionBindings {
+	let mut bindings = ActionBindings::default();
+	if let Some(file) = bindings_assets.get(&config.file) {
+		bindings.apply(&file.bindings);
+	}
+	bindings.apply(&config.saved);
+	for (binding, actions) in bindings.conflicts() {
+		let names: Vec<String> = actions.iter().map(|action| action.get_name()).collect();
+		warn!("{} is bound to more than one action: {}", binding.get_name(), names.join(", "));
+	}
+	bindings
+}
+
+fn load_bindings(
+	mut config: ResMut<BindingsConfig>,
+	mut bindings: ResMut<ActionBindings>,
+	bindings_assets: Res<Assets<BindingsAsset>>,
+	asset_server: Res<AssetServer>,
+	pkv: Res<PkvStore>,
+) {
+	config.file = asset_server.load("controls.bindings.ron");
+	config.saved = load_saved::<BindingMap>(&pkv, "bindings").unwrap_or_default();
+	*bindings = build_bindings(&config, &bindings_assets);
+}
+
+// Picks up the bindings file once it loads, and again whenever it changes
+fn reload_bindings(
+	mut bindings: ResMut<ActionBindings>,
+	mut ev_r_bindings: EventReader<AssetEvent<BindingsAsset>>,
+	bindings_assets: Res<Assets<BindingsAsset>>,
+	config: Res<BindingsConfig>,
+) {
+	for ev in ev_r_bindings.read() {
+		let (AssetEvent::Added{id} | AssetEvent::Modified{id}) = ev else {continue};
+		if *id != config.file.id() {continue;};
+		*bindings = build_bindings(&config, &bindings_assets);
+	}
+}
+
+fn save_bindings(
+	pkv: &mut PkvStore,
+	saved: &BindingMap,
+) {
+	store_saved(pkv, "bindings", saved);
+}
+
+fn toggle_controls(
+	mut next_state: ResMut<NextState<GameState>>,
+	state: Res<State<GameState>>,
+	screen: Res<ControlsScreen>,
+	actions: Res<Actions>,
+) {
+	// Whatever is pressed while capturing is being bound, not acted on
+	if screen.capturing.is_some() {return;};
+	match state.get() {
+		GameState::Game => if actions.just_pressed(Action::Controls) {
+			next_state.set(GameState::Controls);
+		},
+		GameState::Controls => if actions.just_pressed(Action::Controls) || actions.just_pressed(Action::Cancel) {
+			next_state.set(GameState::Game);
+		},
+		_ => (),
+	}
+}
+
+// Drawn over the board, so it covers the view however the board is zoomed or panned
+fn spawn_controls_screen(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	let style = |font_size: f32| TextStyle {
+		font: asset_server.load("fonts/XTypewriter.ttf"),
+		font_size: font_size,
+		color: Color::rgb(0.9, 0.9, 0.9),
+	};
+	commands.spawn((SpriteBundle{
+		transform: Transform::from_xyz(0.0, 0.0, 980.0),
+		sprite: Sprite {
+			custom_size: Some(PANEL_SIZE),
+			color: Color::rgba(0.0, 0.0, 0.0, 0.9),
+			..default()
+		},
+		..default()
+		},
+		ControlsOnly,
+		OVERLAY,
+	)).with_children(|parent| {
+		parent.spawn((Text2dBundle{
+			transform: Transform::from_xyz(0.0, ROW_TOP + 60.0, 5.0),
+			text: Text::from_section("Controls", TextStyle {
+				font: asset_server.load("fonts/XTypewriterBold.ttf"),
+				font_size: 40.0,
+				color: Color::rgb(0.9, 0.7, 0.7),
+			}),
+			..default()
+			},
+			OVERLAY,
+		));
+		for row in 0..=RESET_ROW {
+			let name = ACTIONS.get(row).map_or("Reset to defaults".to_string(), |action| action.get_name());
+			let y = ROW_TOP - row as f32 * ROW_HEIGHT;
+			parent.spawn((Text2dBundle{
+				transform: Transform::from_xyz(-PANEL_SIZE.x/2.0 + 60.0, y, 5.0),
+				text_anchor: bevy::sprite::Anchor::CenterLeft,
+				text: Text::from_section(name, style(24.0)),
+				..default()
+				},
+				ControlsRow(row),
+				OVERLAY,
+			));
+			parent.spawn((Text2dBundle{
+				transform: Transform::from_xyz(-PANEL_SIZE.x/2.0 + 320.0, y, 5.0),
+				text_anchor: bevy::sprite::Anchor::CenterLeft,
+				text: Text::from_section(String::new(), style(20.0)),
+				..default()
+				},
+				ControlsBindingsText(row),
+				OVERLAY,
+			));
+		}
+		parent.spawn((Text2dBundle{
+			transform: Transform::from_xyz(0.0, -PANEL_SIZE.y/2.0 + 40.0, 5.0),
+			text: Text::from_section(String::new(), style(20.0)),
+			..default()
+			},
+			ControlsStatusText,
+			OVERLAY,
+		));
+	});
+}
+
+fn despawn_controls_screen(
+	mut commands: Commands,
+	mut screen: ResMut<ControlsScreen>,
+	controls_query: Query<(Entity, With<ControlsOnly>)>,
+) {
+	for (entity, _) in controls_query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+	*screen = ControlsScreen::default();
+}
+
+// The first new input after choosing a row becomes its binding, unless another action already uses it
+fn capture_binding(
+	mut screen: ResMut<ControlsScreen>,
+	mut config: ResMut<BindingsConfig>,
+	mut bindings: ResMut<ActionBindings>,
+	mut pkv: ResMut<PkvStore>,
+	mut ev_r_scroll: EventReader<MouseWheel>,
+	bindings_assets: Res<Assets<BindingsAsset>>,
+	keyboard: Res<Input<KeyCode>>,
+	mouse: Res<Input<MouseButton>>,
+	gamepad_buttons: Res<Input<GamepadButton>>,
+) {
+	let scroll: f32 = ev_r_scroll.read().map(|ev| ev.y).sum();
+	let Some(replacing) = screen.capturing else {return};
+	let Some(&action) = ACTIONS.get(screen.selected) else {return};
+	if keyboard.just_pressed(KeyCode::Escape) {
+		screen.capturing = None;
+		screen.status = String::new();
+		return;
+	}
+
+	// Modifiers only count alongside another input
+	let modifier_keys = [
+		KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::ControlLeft, KeyCode::ControlRight,
+		KeyCode::AltLeft, KeyCode::AltRight, KeyCode::SuperLeft, KeyCode::SuperRight,
+	];
+	let modifier = held_modifier(&keyboard);
+	let binding = if let Some(key) = keyboard.get_just_pressed().find(|key| !modifier_keys.contains(key)) {
+		Binding::with(InputSource::Key(*key), modifier)
+	} else if let Some(button) = mouse.get_just_pressed().next() {
+		Binding::with(InputSource::Mouse(*button), modifier)
+	} else if scroll > 0.0 {
+		Binding::with(InputSource::WheelUp, modifier)
+	} else if scroll < 0.0 {
+		Binding::with(InputSource::WheelDown, modifier)
+	} else if let Some(button) = gamepad_buttons.get_just_pressed().next() {
+		Binding::new(InputSource::Pad(button.button_type))
+	} else {
+		return;
+	};
+
+	let clashes = bindings.clashes(action, binding);
+	if !clashes.is_empty() {
+		let names: Vec<String> = clashes.iter().map(|clash| clash.get_name()).collect();
+		screen.status = format!("{} is already used for {}, try another or Escape to stop", binding.get_name(), names.join(", "));
+		return;
+	}
+	let mut action_bindings = if replacing {Vec::new()} else {bindings.get(action)};
+	if !action_bindings.contains(&binding) {
+		action_bindings.push(binding);
+	}
+	config.saved.insert(action, action_bindings);
+	save_bindings(&mut pkv, &config.saved);
+	*bindings = build_bindings(&config, &bindings_assets);
+	screen.capturing = None;
+	screen.status = format!("{} now triggers {}", binding.get_name(), action.get_name());
+}
+
+fn navigate_controls(
+	mut screen: ResMut<ControlsScreen>,
+	mut config: ResMut<BindingsConfig>,
+	mut bindings: ResMut<ActionBindings>,
+	mut pkv: ResMut<PkvStore>,
+	bindings_assets: Res<Assets<BindingsAsset>>,
+	panel_query: Query<&GlobalTransform, (With<ControlsOnly>, With<Sprite>)>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
+) {
+	if screen.capturing.is_some() {return;};
+	let rows = RESET_ROW + 1;
+	// The mouse picks whichever row it's over
+	let panel = panel_query.get_single().ok().map(|panel_pos| panel_pos.affine().inverse());
+	if let (Some(cursor_pos), Some(panel)) = (pointer.ui.filter(|_| pointer.is_changed() && !pointer.snapped), panel) {
+		let cursor_pos = panel.transform_point3(cursor_pos.extend(0.0)).xy();
+		let row = ((ROW_TOP + ROW_HEIGHT/2.0 - cursor_pos.y) / ROW_HEIGHT).floor();
+		if cursor_pos.x.abs() < PANEL_SIZE.x/2.0 && row >= 0.0 && (row as usize) < rows && screen.selected != row as usize {
+			screen.selected = row as usize;
+		}
+	}
+	if actions.just_pressed(Action::Up) {
+		screen.selected = (screen.selected + rows - 1) % rows;
+	}
+	if actions.just_pressed(Action::Down) {
+		screen.selected = (screen.selected + 1) % rows;
+	}
+
+	let Some(action) = ACTIONS.get(screen.selected) else {
+		if actions.just_pressed(Action::Select) {
+			config.saved.clear();
+			save_bindings(&mut pkv, &config.saved);
+			*bindings = build_bindings(&config, &bindings_assets);
+			screen.status = "Controls reset".to_string();
+		}
+		return;
+	};
+	if actions.just_pressed(Action::Select) {
+		screen.capturing = Some(false);
+		screen.status = format!("Press an input to add to {}, Escape to stop", action.get_name());
+	} else if actions.just_pressed(Action::Unravel) {
+		screen.capturing = Some(true);
+		screen.status = format!("Press an input to replace {}, Escape to stop", action.get_name());
+	}
+}
+
+fn update_controls_screen(
+	mut row_query: Query<(&mut Text, &ControlsRow)>,
+	mut bindings_text_query: Query<(&mut Text, &ControlsBindingsText, Without<ControlsRow>)>,
+	mut status_text_query: Query<(&mut Text, (With<ControlsStatusText>, Without<ControlsRow>, Without<ControlsBindingsText>))>,
+	screen: Res<ControlsScreen>,
+	bindings: Res<ActionBindings>,
+	added_query: Query<(), Added<ControlsStatusText>>,
+) {
+	if !screen.is_changed() && !bindings.is_changed() && added_query.is_empty() {return;};
+	let conflicts = bindings.conflicts();
+	for (mut text, row) in row_query.iter_mut() {
+		text.sections[0].style.color = if row.0 == screen.selected {SELECTED_COLOR} else {Color::rgb(0.9, 0.9, 0.9)};
+	}
+	for (mut text, bindings_text, _) in bindings_text_query.iter_mut() {
+		let Some(&action) = ACTIONS.get(bindings_text.0) else {continue};
+		let action_bindings = bindings.get(action);
+		let names: Vec<String> = action_bindings.iter().map(|binding| binding.get_name()).collect();
+		text.sections[0].value = if names.is_empty() {"-".to_string()} else {names.join(", ")};
+		// Bindings shared with another action show up in red
+		let conflicted = conflicts.iter().any(|(_, actions)| actions.contains(&action));
+		text.sections[0].style.color = if conflicted {CONFLICT_COLOR} else {Color::rgb(0.7, 0.7, 0.7)};
+	}
+	for (mut text, _) in status_text_query.iter_mut() {
+		text.sections[0].value = if screen.status.is_empty() {
+			// Named after whatever they're currently bound to
+			let first = |action: Action| bindings.get(action).first().map_or(action.get_name(), |binding| binding.get_name());
+			format!("{} adds a binding, {} replaces them, {} closes",
+				first(Action::Select), first(Action::Unravel), first(Action::Cancel))
+		} else {
+			screen.status.clone()
+		};
+	}
+}
@@ -2,7 +2,7 @@
 use bevy::prelude::*;
 use bevy_editor_pls::EditorPlugin;
 
-use crate::{tiles::spawn_tile, derivables::*};
+use crate::{tiles::spawn_tile, threads::spawn_thread, derivables::*};
 
 pub struct DebugPlugin;
 
@@ -13,11 +13,18 @@ impl Plugin for DebugPlugin {
 			.add_systems(Update, (
 				spawn_random_tile,
 				dump_locs,
-			).run_if(in_state(GameState::Game)))
+			).run_if(in_state(GameState::Game).and_then(alt_held)))
 		;
 	}
 }
 
+// Debug keys sit behind alt so they don't fight the board controls
+fn alt_held(
+	keyboard: Res<Input<KeyCode>>,
+) -> bool {
+	keyboard.pressed(KeyCode::AltLeft) || keyboard.pressed(KeyCode::AltRight)
+}
+
 fn spawn_random_tile(
 	mut commands: Commands,
 	asset_server: Res<AssetServer>,
@@ -40,7 +47,7 @@ fn spawn_random_tile(
 
 		let loc = Vec3::new(-200.0, -200.0, 300.0);
 
-		let tile = spawn_tile(&mut commands, loc, *tile_type, &asset_server, 3, false, -1, false, tack_tile);
+		let tile = spawn_tile(&mut commands, loc, *tile_type, &asset_server, NEUTRAL_GROUP, false, -1, false, tack_tile);
 	}
 	if keyboard.just_pressed(KeyCode::W) {
 		*waste_count += 1;
@@ -53,7 +60,7 @@ fn spawn_random_tile(
 
 		let loc = Vec3::new(-200.0, -200.0, 300.0);
 
-		let tile = spawn_tile(&mut commands, loc, tile_type, &asset_server, 4, false, *waste_count, false, tack_tile);
+		let tile = spawn_tile(&mut commands, loc, tile_type, &asset_server, JUNK_GROUP, false, *waste_count, false, tack_tile);
 	}
 	if keyboard.just_pressed(KeyCode::C) {
 		let mut iter = tack_query.iter_combinations();
@@ -65,28 +72,7 @@ fn spawn_random_tile(
 			let tacks = [tack_a_entity, tack_b_entity].to_vec();
 			let tacks_locs = [tack_a_pos.translation().xy(), tack_b_pos.translation().xy()].to_vec();
 			if (tack_a.links-tack_b.links).abs() == 1 {
-				let thread_entity = commands.spawn((
-					SpriteBundle {
-						transform: Transform::from_xyz(
-							(tack_a_pos.translation().x + tack_b_pos.translation().x)/2.0,
-							(tack_a_pos.translation().y + tack_b_pos.translation().y)/2.0,
-							500.0,
-						),
-						sprite: Sprite {
-							custom_size: Some(Vec2::new(6.0, 1.0)),
-							color: get_tack_color(4),
-							..default()
-						},
-						..default()
-					},
-					Thread {
-						group: 4,
-						index: -1.0,
-						tacks: tacks,
-						tacks_locs: tacks_locs,
-					},
-					RemoveOnReset,
-				)).id();
+				spawn_thread(&mut commands, JUNK_GROUP, -1.0, tacks, tacks_locs);
 			}
 		}
 	}
@@ -95,18 +81,14 @@ fn spawn_random_tile(
 fn dump_locs(
 	keyboard: Res<Input<KeyCode>>,
 	tile_query: Query<(&Transform, &Tile)>,
-	grid_query: Query<(&Transform, With<Grid>)>,
+	board: Res<BoardConfig>,
 ) {
 	if keyboard.just_pressed(KeyCode::D) {
 		for (tile_pos, tile) in tile_query.iter() {
-			for (grid_pos, _) in grid_query.iter() {
-				if (tile_pos.translation.y - grid_pos.translation.x).abs() < GRID_SIZE.x/2.0 
-				&& (tile_pos.translation.y - grid_pos.translation.y).abs() < GRID_SIZE.y/2.0 {
-					println!("{:?} at {}", tile.0, tile_pos.translation);
-					println!("Coords: {:?}", grid_to_index([tile_pos.translation].to_vec()));
-				}
+			if board.contains(tile_pos.translation.xy()) {
+				println!("{:?} at {}", tile.0, tile_pos.translation);
+				println!("Coords: {:?}", board.grid_to_index([tile_pos.translation].to_vec()));
 			}
 		}
 	}
 }
-
@@ -1,6 +1,9 @@
 // Import Bevy game engine essentials
-use bevy::prelude::*;
+use bevy::{prelude::*, render::view::RenderLayers, sprite::Anchor, utils::{HashMap, HashSet}};
 use bevy_kira_audio::AudioInstance;
+use serde::{Deserialize, Serialize};
+
+use crate::{board::{Board, Verdict}, geometry::Segment, levels::{CampaignAsset, LevelAsset, level_path}};
 
 // CONTENTS
 // - Constants
@@ -12,13 +15,19 @@ use bevy_kira_audio::AudioInstance;
 
 // CONSTANTS
 pub const VIEW_SIZE: Vec2 = Vec2::new(1600.0, 900.0);
+// Where the evidence board is painted on the background, the board camera zooms and pans inside it
+pub const BOARD_VIEW_CENTRE: Vec2 = Vec2::new(-180.0, 200.0);
+pub const BOARD_VIEW_SIZE: Vec2 = Vec2::new(1160.0, 480.0);
+// Case reports and textboxes are drawn over the board by a fixed camera instead of zooming with it
+pub const OVERLAY: RenderLayers = RenderLayers::layer(1);
+// Only the board camera draws the board, so the fixed view doesn't show an unzoomed copy under it
+pub const BOARD_LAYER: RenderLayers = RenderLayers::layer(2);
 
 pub const SFX_VOLUME: f64 = 1.0;
 pub const BGM_VOLUME: f64 = 1.0;
 
-pub const GRID_SIZE: Vec2 = Vec2::new(1160.0, 480.0);
-pub const GRID_CELLS: Vec2 = Vec2::new(GRID_SIZE.x/40.0, GRID_SIZE.y/40.0);
-pub const CELL_SIZE: f32 = GRID_SIZE.x/GRID_CELLS.x;
+// Board size and placement come from each level's BoardConfig
+pub const CELL_SIZE: f32 = 40.0;
 pub const H_CELL_SIZE: f32 = CELL_SIZE/2.0;
 
 pub const TILE_OFFSETS: [[Vec2; 4]; 4] = [
@@ -32,11 +41,28 @@ pub const TILE_OFFSETS: [[Vec2; 4]; 4] = [
 	Vec2::new(3.0*H_CELL_SIZE, -1.0*H_CELL_SIZE), Vec2::new(3.0*H_CELL_SIZE, -3.0*H_CELL_SIZE)],
 ];
 
+// Cases use thread groups 0 to MAX_CASES - 1, the groups after them are reserved
+pub const MAX_CASES: usize = 6;
+// Evidence that hasn't been threaded into a case yet
+pub const NEUTRAL_GROUP: usize = MAX_CASES;
+// Junk strung together at level load
+pub const JUNK_GROUP: usize = MAX_CASES + 1;
+// Routing pins threads can wrap around
+pub const PIN_GROUP: usize = MAX_CASES + 2;
+
+pub const CASE_COLORS: [Color; MAX_CASES] = [
+	Color::RED, Color::GREEN, Color::BLUE,
+	Color::YELLOW, Color::CYAN, Color::FUCHSIA,
+];
+
+// Points each thread's rope is simulated with
+pub const ROPE_POINTS: usize = 13;
+
 pub const NUM_1_JUNK: usize = 6;
 pub const NUM_2_JUNK: usize = 6;
 pub const NUM_3_JUNK: usize = 3;
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub enum TileType {
 	B,
 	I,
@@ -49,6 +75,8 @@ pub enum TileType {
 	V(usize),
 	C(usize),
 	W(usize),
+	// Routing pin, threads bend around it without it counting as evidence
+	P,
 }
 
 impl TileType {
@@ -63,11 +91,23 @@ impl TileType {
 			TileType::L => [(0,0), (0,1), (0,2), (1,2)].to_vec(),
 			TileType::J => [(1,0), (1,1), (1,2), (0,2)].to_vec(),
 			TileType::V(_) => [(0,0), (1,0), (2,0), (0,1), (1,1), (2,1)].to_vec(),
-			TileType::W(1) => [(0,0)].to_vec(),
+			TileType::W(1) | TileType::P => [(0,0)].to_vec(),
 			TileType::W(_) => [(0,0), (1,0), (2,0), (0,1), (1,1), (2,1), (0,2), (1,2), (2,2)].to_vec(),
 		}
 	}
 
+	// Shapes that are their own mirror image keep their art the right way round when mirrored
+	pub fn is_symmetric(&self) -> bool {
+		let layout = self.get_layout();
+		let width = self.get_width();
+		layout.iter().all(|loc| layout.contains(&(width - 1 - loc.0, loc.1)))
+	}
+
+	// Cells across the widest row of the layout
+	pub fn get_width(&self) -> usize {
+		self.get_layout().iter().map(|loc| loc.0 + 1).max().unwrap_or(1)
+	}
+
 	pub fn get_path(&self) -> String {
 		match *self {
 			TileType::B => "sprites/tiles/crowbar.png".to_string(),
@@ -78,8 +118,11 @@ impl TileType {
 			TileType::Z => "sprites/tiles/lockpick.png".to_string(),
 			TileType::L => "sprites/tiles/handgun.png".to_string(),
 			TileType::J => "sprites/tiles/shells.png".to_string(),
-			TileType::V(i) => format!("sprites/victim_{}.png", i),
-			TileType::C(i) => format!("sprites/suspect_{}.png", i),
+			// Only three portraits of each, later cases reuse them
+			TileType::V(i) => format!("sprites/victim_{}.png", i % 3),
+			TileType::C(i) => format!("sprites/suspect_{}.png", i % 3),
+			// Brass washer the routing pin is pushed through
+			TileType::P => "sprites/tiles/pin.png".to_string(),
 			TileType::W(s) => {
 				match s {
 					1 => {
@@ -95,6 +138,190 @@ impl TileType {
 			}
 		}
 	}
+
+	// Shown as the tooltip title when hovering a tile
+	pub fn get_name(&self) -> String {
+		match *self {
+			TileType::B => "Crowbar".to_string(),
+			TileType::I => "Cotton Swab".to_string(),
+			TileType::O => "Fingerprint".to_string(),
+			TileType::T => "CCTV Still".to_string(),
+			TileType::S => "Handcuffs".to_string(),
+			TileType::Z => "Lockpick".to_string(),
+			TileType::L => "Handgun".to_string(),
+			TileType::J => "Spent Shells".to_string(),
+			TileType::V(i) => format!("Victim {}", i+1),
+			TileType::C(i) => format!("Suspect {}", i+1),
+			TileType::W(_) => "Junk".to_string(),
+			TileType::P => "Routing Pin".to_string(),
+		}
+	}
+
+	pub fn get_description(&self) -> String {
+		match *self {
+			TileType::B => "Bent at the tip and flecked with paint. Someone was in a hurry to get through a door.",
+			TileType::I => "Sealed in a bag and labelled in smudged pen. The lab says it's blood, but not whose.",
+			TileType::O => "A partial print lifted from a window frame. Enough to rule someone in, or out.",
+			TileType::T => "A grainy frame from a shop camera across the road. The timestamp is the interesting part.",
+			TileType::S => "Standard issue and still locked. Whoever wore them didn't stay in them.",
+			TileType::Z => "A neat little set of picks wrapped in cloth. Not the sort of thing you carry by accident.",
+			TileType::L => "Recently fired and wiped clean. Almost clean.",
+			TileType::J => "A handful of casings swept up from the floor. They should match a gun somewhere on this board.",
+			TileType::V(_) => "Where the case starts. Run a thread from here through the evidence.",
+			TileType::C(_) => "Where the case ends. Only one of them did it.",
+			TileType::W(_) => "Notes and clutter from whoever shares the board. Nothing to do with your cases.",
+			TileType::P => "A spare pin for routing threads around the clutter. It doesn't count as evidence.",
+		}.to_string()
+	}
+}
+
+// Everything the player can do on the board, raw input is only read by the actions module
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+pub enum Action {
+	Up,
+	Down,
+	Left,
+	Right,
+	// Pick up and drop tiles, press buttons and pin case files
+	Select,
+	// Start, extend and finish threads
+	Thread,
+	// Drop the thread being drawn
+	Cancel,
+	Unravel,
+	Rotate,
+	RotateBack,
+	Flip,
+	// Jump the cursor between the UI buttons
+	FocusButtons,
+	Undo,
+	Redo,
+	// Cycle the screen filter
+	Filter,
+	// Open and close the controls screen
+	Controls,
+	ZoomIn,
+	ZoomOut,
+	// Drag the camera around the board
+	Pan,
+	// Open and close the level editor
+	Editor,
+	// Editor actions, only read while editing a level
+	EditorPlace,
+	EditorDelete,
+	EditorNextTile,
+	EditorPrevTile,
+	EditorRotate,
+	EditorMirror,
+	EditorLock,
+	EditorTack,
+	EditorGroup,
+	EditorLinks,
+	EditorSolution,
+	EditorCase(usize),
+	EditorAddCase,
+	EditorRemoveCase,
+	EditorReport,
+	EditorBlocking,
+	EditorSave,
+}
+
+// Every action in the order the controls screen lists them
+pub const ACTIONS: [Action; 19] = [
+	Action::Up, Action::Down, Action::Left, Action::Right,
+	Action::Select, Action::Thread, Action::Cancel, Action::Unravel,
+	Action::Rotate, Action::RotateBack, Action::Flip, Action::FocusButtons,
+	Action::Undo, Action::Redo, Action::ZoomIn, Action::ZoomOut,
+	Action::Pan, Action::Filter, Action::Controls,
+];
+
+// Rebound through the bindings file, the controls screen only lists the board's actions
+pub const EDITOR_ACTIONS: [Action; 23] = [
+	Action::Editor, Action::EditorPlace, Action::EditorDelete, Action::EditorNextTile, Action::EditorPrevTile,
+	Action::EditorRotate, Action::EditorMirror, Action::EditorLock, Action::EditorTack,
+	Action::EditorGroup, Action::EditorLinks, Action::EditorSolution,
+	Action::EditorCase(0), Action::EditorCase(1), Action::EditorCase(2),
+	Action::EditorCase(3), Action::EditorCase(4), Action::EditorCase(5),
+	Action::EditorAddCase, Action::EditorRemoveCase, Action::EditorReport, Action::EditorBlocking, Action::EditorSave,
+];
+
+impl Action {
+	pub fn get_name(&self) -> String {
+		match *self {
+			Action::EditorCase(case) => return format!("Select Case {}", case + 1),
+			Action::Up => "Up",
+			Action::Down => "Down",
+			Action::Left => "Left",
+			Action::Right => "Right",
+			Action::Select => "Select",
+			Action::Thread => "Draw Thread",
+			Action::Cancel => "Cancel",
+			Action::Unravel => "Unravel",
+			Action::Rotate => "Rotate",
+			Action::RotateBack => "Rotate Back",
+			Action::Flip => "Flip",
+			Action::FocusButtons => "Buttons",
+			Action::Undo => "Undo",
+			Action::Redo => "Redo",
+			Action::Filter => "Screen Filter",
+			Action::Controls => "Controls",
+			Action::ZoomIn => "Zoom In",
+			Action::ZoomOut => "Zoom Out",
+			Action::Pan => "Pan",
+			Action::Editor => "Level Editor",
+			Action::EditorPlace => "Place / Move Tile",
+			Action::EditorDelete => "Delete Tile",
+			Action::EditorNextTile => "Next Tile Type",
+			Action::EditorPrevTile => "Previous Tile Type",
+			Action::EditorRotate => "Rotate Tile",
+			Action::EditorMirror => "Mirror Tile",
+			Action::EditorLock => "Lock Turning",
+			Action::EditorTack => "Move Tack",
+			Action::EditorGroup => "Tack Group",
+			Action::EditorLinks => "Junk Links",
+			Action::EditorSolution => "Toggle In Solution",
+			Action::EditorAddCase => "Add Case",
+			Action::EditorRemoveCase => "Remove Case",
+			Action::EditorReport => "Type Case Report",
+			Action::EditorBlocking => "Threads Block Tiles",
+			Action::EditorSave => "Save Level",
+		}.to_string()
+	}
+
+	// Some actions only do anything while a tile is held, some only while one isn't
+	pub fn while_holding(&self) -> Option<bool> {
+		match *self {
+			Action::Rotate | Action::RotateBack | Action::Flip => Some(true),
+			Action::ZoomIn | Action::ZoomOut => Some(false),
+			_ => None,
+		}
+	}
+
+	// Some actions only do anything in the editor, some only on the board
+	pub fn while_editing(&self) -> Option<bool> {
+		match *self {
+			Action::Editor | Action::Filter => None,
+			Action::EditorPlace | Action::EditorDelete | Action::EditorNextTile | Action::EditorPrevTile
+			| Action::EditorRotate | Action::EditorMirror | Action::EditorLock | Action::EditorTack
+			| Action::EditorGroup | Action::EditorLinks | Action::EditorSolution | Action::EditorCase(_)
+			| Action::EditorAddCase | Action::EditorRemoveCase | Action::EditorReport | Action::EditorBlocking
+			| Action::EditorSave => Some(true),
+			_ => Some(false),
+		}
+	}
+
+	// Whether both actions can fire at once, if not they can share inputs
+	pub fn overlaps(&self, other: Action) -> bool {
+		let holding = match (self.while_holding(), other.while_holding()) {
+			(Some(holding), Some(other_holding)) => holding == other_holding,
+			_ => true,
+		};
+		let editing = match (self.while_editing(), other.while_editing()) {
+			(Some(editing), Some(other_editing)) => editing == other_editing,
+			_ => true,
+		};
+		holding && editing
+	}
 }
 
 // STATES
@@ -103,21 +330,386 @@ pub enum GameState {
 	#[default]
 	Boot,
 	Game,
+	Editor,
+	// Board is frozen while rebinding
+	Controls,
 }
 
 // RESOURCES
 #[derive(Resource)]
 pub struct Level(pub usize);
 
+// Every level in the campaign, in play order, as listed in assets/levels/campaign.ron
+#[derive(Resource, Default)]
+pub struct LevelRegistry {
+	pub campaign: Handle<CampaignAsset>,
+	pub names: Vec<String>,
+	pub handles: Vec<Handle<LevelAsset>>,
+}
+
+impl LevelRegistry {
+	pub fn len(&self) -> usize {
+		self.names.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.names.is_empty()
+	}
+
+	pub fn name(&self, level: usize) -> Option<&str> {
+		self.names.get(level).map(|name| name.as_str())
+	}
+
+	pub fn handle(&self, level: usize) -> Option<&Handle<LevelAsset>> {
+		self.handles.get(level)
+	}
+
+	// Asset path of the level file
+	pub fn path(&self, level: usize) -> Option<String> {
+		self.name(level).map(level_path)
+	}
+
+	pub fn prev(&self, level: usize) -> Option<usize> {
+		level.checked_sub(1)
+	}
+
+	pub fn next(&self, level: usize) -> Option<usize> {
+		if level + 1 < self.len() {Some(level + 1)} else {None}
+	}
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct LevelProgress {
+	pub solved: bool,
+	pub best_moves: Option<u32>,
+	pub best_time: Option<f32>,
+}
+
+// Keyed by level name so reordering the campaign keeps progress
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct Progress {
+	pub levels: HashMap<String, LevelProgress>,
+	// Lets every level be played regardless of what's been solved
+	pub free_play: bool,
+}
+
+impl Progress {
+	pub fn solved(&self, name: &str) -> bool {
+		self.levels.get(name).is_some_and(|level_progress| level_progress.solved)
+	}
+
+	pub fn unlocked(&self, registry: &LevelRegistry, level: usize) -> bool {
+		level == 0 || self.free_play
+		|| registry.name(level - 1).is_some_and(|prev| self.solved(prev))
+	}
+}
+
 #[derive(Resource)]
 pub struct SplashCount(pub usize);
 
 #[derive(Resource)]
-pub struct ThreadCount(pub Vec3);
+pub struct ThreadCount(pub Vec<f32>);
 
 #[derive(Resource)]
 pub struct ThreadColliding(pub bool);
 
+// Board cells where threads from different cases cross
+#[derive(Resource, Default)]
+pub struct ThreadCollisions {
+	pub cells: HashSet<(usize, usize)>,
+}
+
+// Case reports pinned open, bottom to top
+#[derive(Resource, Default)]
+pub struct CaseReportStack {
+	pub order: Vec<Entity>,
+	// Cursor is over a pinned report, so clicks shouldn't reach the board underneath
+	pub hovered: bool,
+}
+
+// Tile under the cursor, None while one is being dragged
+#[derive(Resource, Default)]
+pub struct HoveredTile(pub Option<Entity>);
+
+// Evidence named by the case report word under the cursor
+#[derive(Resource, Default)]
+pub struct HoveredKeyword(pub Option<TileType>);
+
+// Board cells the held tile would cover and whether each is free
+#[derive(Resource, Default)]
+pub struct PlacementPreview {
+	pub cells: Vec<((usize, usize), bool)>,
+}
+
+// Level rule for whether threads stop tiles being placed under them
+#[derive(Resource)]
+pub struct ThreadsBlockTiles(pub bool);
+
+// Thread segments under each board cell's part of their bounding box, with the tiles at either end of them
+#[derive(Resource, Default)]
+pub struct ThreadCells {
+	pub cells: HashMap<(usize, usize), Vec<(Segment, [Entity; 2])>>,
+}
+
+impl ThreadCells {
+	// Segments that might cross a cell, the placement check does the exact test
+	pub fn near(&self, cell: (usize, usize)) -> impl Iterator<Item = &(Segment, [Entity; 2])> {
+		self.cells.get(&cell).into_iter().flatten()
+	}
+}
+
+// Actions held down this frame, and which changed since the last
+#[derive(Resource, Default)]
+pub struct Actions {
+	pressed: HashSet<Action>,
+	just_pressed: HashSet<Action>,
+	just_released: HashSet<Action>,
+}
+
+impl Actions {
+	pub fn pressed(&self, action: Action) -> bool {
+		self.pressed.contains(&action)
+	}
+
+	pub fn just_pressed(&self, action: Action) -> bool {
+		self.just_pressed.contains(&action)
+	}
+
+	pub fn just_released(&self, action: Action) -> bool {
+		self.just_released.contains(&action)
+	}
+
+	// Stops a press that's already been handled from also being read further on this frame
+	pub fn clear_just_pressed(&mut self, action: Action) {
+		self.just_pressed.remove(&action);
+	}
+
+	// Taps are inputs like the scroll wheel that press and release in the same frame
+	pub fn update(&mut self, held: HashSet<Action>, taps: HashSet<Action>) {
+		self.just_pressed = held.difference(&self.pressed).copied().chain(taps).collect();
+		self.just_released = self.pressed.difference(&held).copied().collect();
+		self.pressed = held;
+	}
+}
+
+// Where the player is pointing, following the mouse until keys or a gamepad move the board cursor
+#[derive(Resource, Default)]
+pub struct Pointer {
+	// Over the board this is where the cursor lands through the zoomed board camera
+	pub pos: Option<Vec2>,
+	// Where the cursor is in the fixed view the buttons, workbench and case reports are laid out in
+	pub ui: Option<Vec2>,
+	// Driven by keys or a gamepad and snapped to cells
+	pub snapped: bool,
+	// Driven by a finger, stays where it last lifted
+	pub touch: bool,
+}
+
+impl Pointer {
+	// Whatever follows the pointer is drawn through the board camera while it's over the board view
+	pub fn layer(&self) -> RenderLayers {
+		if self.ui.is_some_and(|ui| BoardView::frame().contains(ui)) {BOARD_LAYER} else {RenderLayers::default()}
+	}
+}
+
+// Plain copy of the board, kept in sync with the ECS for solution checking
+#[derive(Resource, Default)]
+pub struct BoardModel(pub Board);
+
+// Size and placement of the evidence board, set from the level being played
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BoardConfig {
+	// In cells
+	pub width: usize,
+	pub height: usize,
+	// Centre of the board
+	pub origin: Vec2,
+}
+
+impl Default for BoardConfig {
+	fn default() -> Self {
+		Self {
+			width: 29,
+			height: 12,
+			origin: BOARD_VIEW_CENTRE,
+		}
+	}
+}
+
+impl BoardConfig {
+	pub fn size(&self) -> Vec2 {
+		Vec2::new(self.width as f32, self.height as f32) * CELL_SIZE
+	}
+
+	pub fn top_left(&self) -> Vec2 {
+		self.origin + Vec2::new(-self.size().x, self.size().y) / 2.0
+	}
+
+	pub fn contains(&self, loc: Vec2) -> bool {
+		(loc - self.origin).abs().cmplt(self.size() / 2.0).all()
+	}
+
+	// Things resting on the board are drawn by the board camera, the workbench by the fixed view
+	pub fn layer_at(&self, loc: Vec2) -> RenderLayers {
+		if self.contains(loc) {BOARD_LAYER} else {RenderLayers::default()}
+	}
+
+	// Board cell under a point, None off the board
+	pub fn cell_at(&self, loc: Vec2) -> Option<(usize, usize)> {
+		let corner = self.top_left();
+		let x = ((loc.x - corner.x)/CELL_SIZE).floor();
+		let y = ((corner.y - loc.y)/CELL_SIZE).floor();
+		if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {return None;};
+		Some((x as usize, y as usize))
+	}
+
+	// Board cells overlapping a rect, cut off at the edges of the board
+	pub fn cells_in(&self, rect: Rect) -> impl Iterator<Item = (usize, usize)> {
+		let corner = self.top_left();
+		let min_x = (((rect.min.x - corner.x)/CELL_SIZE).floor() as i32).max(0);
+		let max_x = (((rect.max.x - corner.x)/CELL_SIZE).floor() as i32).min(self.width as i32 - 1);
+		let min_y = (((corner.y - rect.max.y)/CELL_SIZE).floor() as i32).max(0);
+		let max_y = (((corner.y - rect.min.y)/CELL_SIZE).floor() as i32).min(self.height as i32 - 1);
+		(min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x as usize, y as usize)))
+	}
+
+	pub fn cell_rect(&self, cell: (usize, usize)) -> Rect {
+		let corner = self.top_left();
+		let min = Vec2::new(corner.x + cell.0 as f32 * CELL_SIZE, corner.y - (cell.1 + 1) as f32 * CELL_SIZE);
+		Rect::from_corners(min, min + Vec2::splat(CELL_SIZE))
+	}
+
+	// Tiles sit on cell corners, so snap the centre of a tile to the nearest one
+	pub fn snap_tile(&self, loc: Vec2) -> Vec2 {
+		let corner = self.top_left();
+		((loc - corner) / CELL_SIZE).round() * CELL_SIZE + corner
+	}
+
+	// Grid indices are the top left cell of a tile's four by four box
+	pub fn index_to_grid(&self, indices: Vec<(usize, usize)>) -> Vec<Vec3> {
+		let corner = self.top_left();
+		let mut locs = Vec::new();
+		for mut index in indices {
+			// Each axis on its own, so an index past one edge doesn't throw the other off
+			index = (index.0.min(self.width.saturating_sub(1)), index.1.min(self.height.saturating_sub(1)));
+			locs.push(Vec3::new(
+				corner.x + (index.0 + 2) as f32 * CELL_SIZE,
+				corner.y - (index.1 + 2) as f32 * CELL_SIZE,
+				300.0,
+			));
+		}
+		locs
+	}
+
+	pub fn grid_to_index(&self, locs: Vec<Vec3>) -> Vec<(usize, usize)> {
+		let corner = self.top_left();
+		let mut indices = Vec::new();
+		for loc in locs {
+			// Rounded, as a snapped tile off the default origin can land a hair short of its cell
+			indices.push((
+				((loc.x - corner.x)/CELL_SIZE - 2.0).round() as usize,
+				((corner.y - loc.y)/CELL_SIZE - 2.0).round() as usize,
+			));
+		}
+		indices
+	}
+}
+
+// What the board camera shows, the board point in the middle of the board view and how far it's zoomed out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoardView {
+	pub centre: Vec2,
+	pub scale: f32,
+}
+
+impl Default for BoardView {
+	fn default() -> Self {
+		Self {
+			centre: BOARD_VIEW_CENTRE,
+			scale: 1.0,
+		}
+	}
+}
+
+impl BoardView {
+	pub fn new(camera_pos: &Transform, projection: &OrthographicProjection) -> Self {
+		Self {
+			centre: camera_pos.translation.xy(),
+			scale: projection.scale,
+		}
+	}
+
+	// Where the board camera draws in the fixed view
+	pub fn frame() -> Rect {
+		Rect::from_center_size(BOARD_VIEW_CENTRE, BOARD_VIEW_SIZE)
+	}
+
+	// Part of the board the camera shows
+	pub fn visible(&self) -> Rect {
+		Rect::from_center_size(self.centre, BOARD_VIEW_SIZE * self.scale)
+	}
+
+	// Where a point in the fixed view lands on the board, None outside the board view
+	pub fn from_ui(&self, ui_pos: Vec2) -> Option<Vec2> {
+		if !Self::frame().contains(ui_pos) {return None;};
+		Some(self.centre + (ui_pos - BOARD_VIEW_CENTRE) * self.scale)
+	}
+
+	// Where a point on the board shows up in the fixed view, the same place if the camera doesn't show it
+	pub fn to_ui(&self, pos: Vec2) -> Vec2 {
+		if !self.visible().contains(pos) {return pos;};
+		BOARD_VIEW_CENTRE + (pos - self.centre) / self.scale
+	}
+}
+
+// Which tile covers each cell of the evidence board, indexed from the top left
+#[derive(Resource)]
+pub struct BoardOccupancy {
+	pub width: usize,
+	pub height: usize,
+	cells: Vec<Option<Entity>>,
+	// Cells each tile covers, so moving a tile only touches its own cells
+	owned: HashMap<Entity, Vec<(usize, usize)>>,
+}
+
+impl BoardOccupancy {
+	pub fn new(width: usize, height: usize) -> Self {
+		Self {
+			width: width,
+			height: height,
+			cells: vec![None; width * height],
+			owned: HashMap::new(),
+		}
+	}
+
+	pub fn get(&self, cell: (usize, usize)) -> Option<Entity> {
+		if cell.0 >= self.width || cell.1 >= self.height {return None;};
+		self.cells[cell.1 * self.width + cell.0]
+	}
+
+	pub fn is_free(&self, cell: (usize, usize)) -> bool {
+		self.get(cell).is_none()
+	}
+
+	// Replace whatever the tile covered before with the given cells
+	pub fn place(&mut self, tile: Entity, cells: Vec<(usize, usize)>) {
+		self.remove(tile);
+		for cell in cells.iter() {
+			self.cells[cell.1 * self.width + cell.0] = Some(tile);
+		}
+		self.owned.insert(tile, cells);
+	}
+
+	pub fn remove(&mut self, tile: Entity) {
+		for cell in self.owned.remove(&tile).unwrap_or_default() {
+			let index = cell.1 * self.width + cell.0;
+			if self.cells[index] == Some(tile) {
+				self.cells[index] = None;
+			}
+		}
+	}
+}
+
 #[derive(Resource)]
 pub struct MusicHandle(pub Handle<AudioInstance>);
 
@@ -134,9 +726,35 @@ pub struct WaitForJunkEvent();
 #[derive(Event)]
 pub struct SolveCaseEvent();
 
+#[derive(Event)]
+pub struct SolveResultEvent {
+	pub verdict: Verdict,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BoardAction {
+	Load,
+	Resume,
+	Restart,
+	MoveTile,
+	DrawThread,
+	Unravel,
+}
+
+#[derive(Event)]
+pub struct BoardActionEvent(pub BoardAction);
+
+#[derive(Event)]
+pub struct UndoEvent();
+
+#[derive(Event)]
+pub struct RedoEvent();
+
 #[derive(Event)]
 pub struct LevelSelectedEvent{
 	pub level: usize,
+	// Coming back from the editor, so the level's saved board and history no longer fit it
+	pub edited: bool,
 }
 
 // COMPONENTS
@@ -146,6 +764,9 @@ pub struct RemoveOnReset;
 #[derive(Component)]
 pub struct LevelText;
 
+#[derive(Component)]
+pub struct FreePlayText;
+
 #[derive(Component)]
 pub struct SolveText;
 
@@ -153,11 +774,32 @@ pub struct SolveText;
 pub struct FailText;
 
 #[derive(Component)]
-pub struct CaseFileText(pub usize);
+pub struct VerdictText;
+
+#[derive(Component)]
+pub struct CaseFileText {
+	// Evidence each text section is tagged with, None for plain text
+	pub keywords: Vec<Option<TileType>>,
+}
 
 #[derive(Component)]
 pub struct HintText;
 
+#[derive(Component)]
+pub struct Tooltip;
+
+// Highlight that follows the pointer while it's driven by keys or a gamepad
+#[derive(Component)]
+pub struct BoardCursor;
+
+// Fixed camera over the whole view
+#[derive(Component)]
+pub struct ViewCamera;
+
+// Zooms and pans over the evidence board, drawn into the board's part of the view
+#[derive(Component)]
+pub struct BoardCamera;
+
 #[derive(Component)]
 pub struct UIButton {
 	pub size: Vec2,
@@ -170,11 +812,57 @@ pub struct Splash(pub usize);
 #[derive(Component)]
 pub struct Tile(pub TileType);
 
+// Colour a tile had before a case report keyword highlighted it
 #[derive(Component)]
-pub struct Tilette;
+pub struct KeywordHighlight(pub Color);
+
+// Index of the tile in its level file
+#[derive(Component)]
+pub struct TileId(pub usize);
+
+// Quarter turns applied through the tile's transform, mirroring moves its tilettes and tack across instead
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Orientation {
+	// Quarter turns anticlockwise
+	pub rotation: u8,
+	pub mirrored: bool,
+	// Set by the level to stop the player turning or flipping the tile
+	pub locked: bool,
+}
+
+impl Orientation {
+	pub fn rotate(&mut self, turns: i32) {
+		self.rotation = (self.rotation as i32 + turns).rem_euclid(4) as u8;
+	}
+
+	// Only turns the tile, mirroring moves its tilettes and tack instead so the art isn't flipped
+	pub fn apply(&self, transform: &mut Transform) {
+		transform.rotation = Quat::from_rotation_z(self.rotation as f32 * std::f32::consts::FRAC_PI_2);
+	}
+
+	// Where a cell of the tile layout sits on the unturned tile, mirrored before rotating
+	pub fn offset(&self, loc: (usize, usize)) -> Vec2 {
+		let offset = TILE_OFFSETS[loc.0][loc.1];
+		if self.mirrored {Vec2::new(-offset.x, offset.y)} else {offset}
+	}
+
+	// Lines the art up with the mirrored cells, shapes that are their own mirror image slide across unflipped
+	pub fn apply_art(&self, tile_type: TileType, sprite: &mut Sprite) {
+		let flip = self.mirrored && !tile_type.is_symmetric();
+		let slide = if self.mirrored && !flip {(4 - tile_type.get_width()) as f32 / 4.0} else {0.0};
+		sprite.flip_x = flip;
+		sprite.anchor = if slide == 0.0 {Anchor::Center} else {Anchor::Custom(Vec2::new(-slide, 0.0))};
+	}
+}
+
+// Cell of the tile layout it covers
+#[derive(Component)]
+pub struct Tilette(pub (usize, usize));
 
 #[derive(Component)]
 pub struct Tack {
+	// Cell of the tile layout it sits on
+	pub cell: (usize, usize),
 	pub group: usize,
 	pub end: bool,
 	pub used: bool,
@@ -187,10 +875,61 @@ pub struct Tack {
 pub struct Thread {
 	pub group: usize,
 	pub index: f32,
+	// Tacks the thread runs through in order, routing pins sit between the two ends
 	pub tacks: Vec<Entity>,
 	pub tacks_locs: Vec<Vec2>,
 }
 
+impl Thread {
+	// Straight runs between consecutive tacks
+	pub fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+		self.tacks_locs.windows(2).map(|locs| Segment::new(locs[0], locs[1]))
+	}
+}
+
+// Verlet points a thread is drawn through, collisions still use the straight lines between its tacks
+#[derive(Component)]
+pub struct Rope {
+	// ROPE_POINTS per run between tacks, sharing the point on each tack
+	pub points: Vec<Vec2>,
+	pub previous: Vec<Vec2>,
+	// Rope length over the distance between its ends
+	pub slack: f32,
+}
+
+impl Rope {
+	pub fn new(anchors: &[Vec2]) -> Self {
+		let mut rope = Self {
+			points: [anchors[0]].to_vec(),
+			previous: [anchors[0]].to_vec(),
+			slack: 1.0,
+		};
+		// A thread that's just been started runs from its tack to itself until the cursor moves
+		rope.extend(*anchors.get(1).unwrap_or(&anchors[0]));
+		for &anchor in anchors.iter().skip(2) {
+			rope.extend(anchor);
+		}
+		rope
+	}
+
+	// Add a straight run from the end of the rope
+	pub fn extend(&mut self, anchor: Vec2) {
+		let start = *self.points.last().unwrap();
+		for i in 1..ROPE_POINTS {
+			let point = start.lerp(anchor, i as f32 / (ROPE_POINTS - 1) as f32);
+			self.points.push(point);
+			self.previous.push(point);
+		}
+	}
+
+	pub fn runs(&self) -> usize {
+		(self.points.len() - 1) / (ROPE_POINTS - 1)
+	}
+}
+
+#[derive(Component)]
+pub struct RopeSegment(pub usize);
+
 #[derive(Component)]
 pub struct Loose;
 
@@ -200,12 +939,19 @@ pub struct Immovable;
 #[derive(Component)]
 pub struct Grid;
 
+// Board cell the highlight sits over, indexed from the top left
+#[derive(Component)]
+pub struct Highlight(pub (usize, usize));
+
+// Translucent copy of the held tile showing where it will land
 #[derive(Component)]
-pub struct Highlight;
+pub struct PlacementGhost;
 
 #[derive(Component)]
 pub struct CaseReport {
     pub number: usize,
+	// Half the hover area's width, narrower when the files overlap
+	pub half_width: f32,
 	pub sfx: bool,
 }
 
@@ -214,239 +960,111 @@ pub struct OpenCaseReport {
 	pub number: usize,
 }
 
+#[derive(Component)]
+pub struct PinnedCaseReport {
+	// Where the report rests when it isn't docked
+	pub home: Vec2,
+	pub docked: bool,
+}
+
 #[derive(Component)]
 pub struct Held {
 	pub origin: Vec3,
 	pub offset: Vec2,
+	// Orientation to return to if the drop is refused
+	pub orientation: Orientation,
 }
 
 
 // HELPER FUNCTIONS
-pub fn cursor_to_screen(
-	cursor_pos: Vec2,
-	window: &Window,
-) -> Vec2 {
-	let screen_cursor = Vec2::new(
-		cursor_pos.x/window.width() * VIEW_SIZE.x - VIEW_SIZE.x/2.0,
-		-cursor_pos.y/window.height() * VIEW_SIZE.y + VIEW_SIZE.y/2.0,
-	);
-	screen_cursor
-}
 
 pub fn get_tack_color(
 	tack_group: usize,
 ) -> Color {
 	match tack_group {
-		0 => Color::RED,
-		1 => Color::GREEN,
-		2 => Color::BLUE,
-		3 => Color::WHITE,
+		NEUTRAL_GROUP => Color::WHITE,
+		PIN_GROUP => Color::GRAY,
+		group if group < MAX_CASES => CASE_COLORS[group],
 		_ => Color::BLACK,
 	}
 }
 
-pub fn get_case_report(
-	level: usize,
+// Case files only have art for three cases, so each is tinted a lighter shade of its threads' colour
+pub fn get_case_tint(
 	case: usize,
-) -> String {
-	match level {
-		0 => match case {
-			0 => "Victim found dead in their living room. Ironic. Suspect broke in through window, DNA sample and fingerprints acquired from broken glass.".to_string(),
-			1 => "Suspect seen fleeing the scene of the crime. A lot of bullet casings were found around the victim.".to_string(),
-			_ => "Autopsy reports victim was killed by blunt force to the head. No loud noises reported by witnesses. Firearm was acquired by police after suspect tried selling it in an auction lot.".to_string(),
-		}
-		1 => match case {
-			0 => "Victim found shot dead after witnesses claim they were trying to summon a demonic entity in a parking lot. Suspect turned themselves in and were handcuffed.".to_string(),
-			1 => "Victim killed in gang crime after being selected by lot. Footage of the crime was recovered by security camera.".to_string(),
-			_ => "Lockpick set acquired from scene of the crime after suspect was killed in their home, no sign of forced entry.".to_string(),
-		}
-		_ => match case {
-			0 => "Victim found with a lot of bullet holes in them. Several bullet casings found leading past their home.".to_string(),
-			1 => "Door to victim's apartment found prized open. Fingerprints recovered from a dropped lottery ticket.".to_string(),
-			_ => "No signs of murder weapon. Security footage shows suspect entering and leaving location of crime.".to_string(),
-		}
-	}
+) -> Color {
+	let [r, g, b, _] = CASE_COLORS[case % MAX_CASES].as_rgba_f32();
+	Color::rgb(0.5 + r * 0.5, 0.5 + g * 0.5, 0.5 + b * 0.5)
 }
 
-pub fn get_level_layout(
-	level: usize,
-) -> Vec<Vec3> {
-	let mut tiles = Vec::new();
-	match level {
-		0 => {
-			tiles.append(&mut index_to_grid([
-				// Victims
-				(10, 3), (15, 3), (20, 3),
-				// Suspects
-				(10, 7), (20, 7), (15, 7),
-				// Junk
-				(0, 5), (5, 0),
-				(26, 9), (25, 4), (16, 0),
-			].to_vec()));
-			tiles.append( &mut [
-				// Evidence
-				Vec3::new(-500.0, -350.0, 300.0),
-				Vec3::new(-500.0, -150.0, 300.0),
-				Vec3::new(-350.0, -350.0, 300.0),
-				Vec3::new(-350.0, -150.0, 300.0),
-			].to_vec());
-			tiles
-		}
-		1 => {
-			tiles.append(&mut index_to_grid([
-				// Victims
-				(0, 0), (15, 10), (25, 3), 
-				// Suspects
-				(3, 7), (9, 2), (2, 4),
-				// Junk
-				(3, 0), (4, 2),
-				(23, 7), (13, 5), (3, 9)
-			].to_vec()));
-			tiles.append( &mut [
-				// Evidence
-				Vec3::new(-500.0, -350.0, 300.0),
-				Vec3::new(-500.0, -150.0, 300.0),
-				Vec3::new(-350.0, -350.0, 300.0),
-				Vec3::new(-350.0, -150.0, 300.0),
-			].to_vec());
-			tiles
-		}
-		_ => {
-			tiles.append(&mut index_to_grid([
-				// Victims
-				(0, 0), (4, 0), (8, 0), 
-				// Suspects
-				(0, 4), (4, 4), (8, 4),
-				// Junk
-				(0, 6), (8, 6),
-				(22, 6), (8, 10), (11, 0)
-			].to_vec()));
-			tiles.append( &mut [
-				// Evidence
-				Vec3::new(-500.0, -350.0, 300.0),
-				Vec3::new(-500.0, -150.0, 300.0),
-				Vec3::new(-350.0, -350.0, 300.0),
-				Vec3::new(-350.0, -150.0, 300.0),
-				Vec3::new(-100.0, -350.0, 300.0),
-			].to_vec());
-			tiles
-		}
-	}
+// Centres of the cells a tile covers when placed with the given orientation and transform
+pub fn tilette_locations(
+	tile_type: TileType,
+	orientation: &Orientation,
+	transform: &Transform,
+) -> Vec<Vec2> {
+	tile_type.get_layout().iter()
+		.map(|loc| transform.transform_point(orientation.offset(*loc).extend(0.0)).xy())
+		.collect()
 }
 
-pub fn get_tile_types(
-	level: usize,
-) -> Vec<TileType> {
-	match level {
-		0 => {
-			[TileType::V(0), TileType::V(1), TileType::V(2),
-			TileType::C(0), TileType::C(1), TileType::C(2),
-			TileType::W(1), TileType::W(1),
-			TileType::W(3),	TileType::W(1), TileType::W(2),
-			TileType::L, TileType::J, TileType::I,
-			TileType::O,
-			].to_vec()
-		}
-		1 => {
-			[TileType::V(0), TileType::V(1), TileType::V(2),
-			TileType::C(0), TileType::C(1), TileType::C(2),
-			TileType::W(1), TileType::W(1),
-			TileType::W(3),	TileType::W(1), TileType::W(2),
-			TileType::T, TileType::S, TileType::L,
-			TileType::Z,
-			].to_vec()
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::{rngs::StdRng, Rng};
+	use crate::testing::check_cases;
+
+	fn random_board(
+		rng: &mut StdRng,
+	) -> BoardConfig {
+		BoardConfig {
+			width: rng.gen_range(1..40),
+			height: rng.gen_range(1..20),
+			origin: Vec2::new(rng.gen_range(-2000.0..2000.0), rng.gen_range(-2000.0..2000.0)),
 		}
-		_ => {
-			[TileType::V(0), TileType::V(1), TileType::V(2),
-			TileType::C(0), TileType::C(1), TileType::C(2),
-			TileType::W(1), TileType::W(1),
-			TileType::W(2),	TileType::W(2), TileType::W(2),
-			TileType::O, TileType::J, TileType::B,
-			TileType::L, TileType::T,
-			].to_vec()
+	}
+
+	#[test]
+	fn grid_indices_round_trip_off_the_default_origin() {
+		check_cases(25, |rng| {
+			let board = random_board(rng);
+			let index = (rng.gen_range(0..board.width), rng.gen_range(0..board.height));
+			let loc = board.index_to_grid(vec![index])[0];
+			assert_eq!(board.grid_to_index(vec![loc]), vec![index], "{:?} on {:?}", index, board);
+			// Tiles are placed two cells in from their index, which is inside the board away from the far edges
+			if index.0 + 2 < board.width && index.1 + 2 < board.height {
+				let centre = loc.truncate() + Vec2::new(CELL_SIZE, -CELL_SIZE) / 2.0;
+				assert_eq!(board.cell_at(centre), Some((index.0 + 2, index.1 + 2)), "{:?} on {:?}", index, board);
+			}
+		});
+	}
+
+	#[test]
+	fn grid_indices_clamp_each_axis_to_the_board() {
+		let board = BoardConfig {width: 10, height: 5, origin: Vec2::new(333.0, -71.0)};
+		assert_eq!(board.index_to_grid(vec![(0, 50)]), board.index_to_grid(vec![(0, 4)]));
+		assert_eq!(board.index_to_grid(vec![(50, 0)]), board.index_to_grid(vec![(9, 0)]));
+		assert_eq!(board.index_to_grid(vec![(50, 50)]), board.index_to_grid(vec![(9, 4)]));
+	}
+
+	#[test]
+	fn mirrored_art_covers_the_mirrored_cells() {
+		let tile_types = [TileType::B, TileType::I, TileType::O, TileType::T, TileType::S, TileType::Z,
+			TileType::L, TileType::J, TileType::V(0), TileType::C(0), TileType::W(1), TileType::W(2), TileType::W(3), TileType::P];
+		for tile_type in tile_types {
+			let orientation = Orientation {mirrored: true, ..default()};
+			let mut sprite = Sprite::default();
+			orientation.apply_art(tile_type, &mut sprite);
+			// Where each cell of the art ends up once the sprite is flipped and slid
+			let slide = -sprite.anchor.as_vec().x * CELL_SIZE * 4.0;
+			let art: Vec<Vec2> = tile_type.get_layout().iter().map(|loc| {
+				let offset = TILE_OFFSETS[loc.0][loc.1];
+				Vec2::new(if sprite.flip_x {-offset.x} else {offset.x} + slide, offset.y)
+			}).collect();
+			let cells: Vec<Vec2> = tile_type.get_layout().iter().map(|loc| orientation.offset(*loc)).collect();
+			assert!(cells.iter().all(|cell| art.iter().any(|loc| loc.distance(*cell) < 0.01)), "{:?}", tile_type);
+			// Only shapes that change when mirrored get their art flipped
+			assert_eq!(sprite.flip_x, matches!(tile_type, TileType::S | TileType::Z | TileType::L | TileType::J), "{:?}", tile_type);
 		}
 	}
 }
-
-pub fn get_tile_group(
-	level: usize,
-) -> Vec<usize> {
-	match level {
-		0 | 1 => [
-			0,1,2,
-			3,3,3,
-			4,4,4,4,4,
-			3,3,3,3
-		].to_vec(),
-		_ => [
-			0,1,2,
-			3,3,3,
-			4,4,4,4,4,
-			3,3,3,3,3
-		].to_vec(),
-	}
-}
-
-pub fn get_junk_links(
-	level: usize,
-) -> Vec<i32> {
-	match level {
-		_ => [
-			-1,-1,-1,
-			-1,-1,-1,
-			1,2,
-			4,5,6,
-			-1,-1,-1,-1,-1,
-		].to_vec(),
-	}
-}
-
-// 99 for random tack location
-pub fn get_tack_tilette(
-	level: usize,
-) -> Vec<usize> {
-	match level {
-		0 | 1 => [
-			0,3,5,
-			0,1,3,
-			0,0,
-			7,0,2,
-			0,2,3,2
-		].to_vec(),
-		_ => [
-			0,3,5,
-			0,1,3,
-			0,0,
-			2,1,3,
-			0,2,3,2,1
-		].to_vec(),			
-	}
-}
-
-pub fn index_to_grid(
-	indices: Vec<(usize, usize)>,
-) -> Vec<Vec3> {
-	let mut locs = Vec::new();
-	for mut index in indices {
-		index = index.clamp((0,0), (28, 11));
-		locs.push(Vec3::new(
-			-680.0 + index.0 as f32 * 40.0,
-			360.0 - index.1 as f32 * 40.0,
-			300.0,
-		));
-	}
-	locs
-}
-
-pub fn grid_to_index(
-	locs: Vec<Vec3>,
-) -> Vec<(usize, usize)> {
-	let mut indices = Vec::new();
-	for loc in locs {
-		indices.push((
-			((loc.x + 680.0)/40.0) as usize,
-			((loc.y - 360.0)/-40.0) as usize,
-		));
-	}
-	indices
-}
\ No newline at end of file
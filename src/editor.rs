@@ -0,0 +1,645 @@
+// Editor module for building levels in game and saving them as level files
+use bevy::{prelude::*, render::view::RenderLayers, window::PrimaryWindow};
+use bevy_pkv::PkvStore;
+
+use crate::{actions::ActionBindings, derivables::*, levels::*, saves::clear_board};
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_systems(Update, (
+				toggle_editor,
+			).run_if(in_state(GameState::Game).or_else(in_state(GameState::Editor))))
+			.add_systems(OnEnter(GameState::Editor), (
+				spawn_editor_ui,
+			))
+			.add_systems(OnExit(GameState::Editor), (
+				despawn_editor_ui,
+			))
+			.add_systems(Update, (
+				type_case_report,
+				edit_board,
+				save_level,
+				respawn_board,
+				update_ghost,
+				update_editor_panel,
+			).chain().run_if(in_state(GameState::Editor)))
+		;
+	}
+}
+
+const PALETTE: [TileType; 24] = [
+	TileType::B, TileType::I, TileType::O, TileType::T,
+	TileType::S, TileType::Z, TileType::L, TileType::J,
+	TileType::V(0), TileType::V(1), TileType::V(2),
+	TileType::V(3), TileType::V(4), TileType::V(5),
+	TileType::C(0), TileType::C(1), TileType::C(2),
+	TileType::C(3), TileType::C(4), TileType::C(5),
+	TileType::W(1), TileType::W(2), TileType::W(3),
+	TileType::P,
+];
+
+#[derive(Resource)]
+struct EditorState {
+	level: LevelAsset,
+	// Spawned tile for each entry in level.tiles
+	tiles: Vec<Entity>,
+	palette: usize,
+	case: usize,
+	typing: bool,
+	// Tile being moved and its offset from the cursor
+	dragging: Option<(usize, Vec2)>,
+	dirty: bool,
+	status: String,
+}
+
+#[derive(Component)]
+struct EditorOnly;
+
+#[derive(Component)]
+struct EditorGhost(usize);
+
+#[derive(Component)]
+struct EditorPanelText;
+
+fn toggle_editor(
+	mut commands: Commands,
+	mut next_state: ResMut<NextState<GameState>>,
+	mut level_assets: ResMut<Assets<LevelAsset>>,
+	mut ev_w_level: EventWriter<LevelSelectedEvent>,
+	editor: Option<Res<EditorState>>,
+	state: Res<State<GameState>>,
+	level: Res<Level>,
+	registry: Res<LevelRegistry>,
+	actions: Res<Actions>,
+) {
+	if !actions.just_pressed(Action::Editor) {return;};
+	match state.get() {
+		GameState::Game => {
+			if let Some(level_asset) = registry.handle(level.0).and_then(|handle| level_assets.get(handle)) {
+				commands.insert_resource(EditorState {
+					level: level_asset.clone(),
+					tiles: Vec::new(),
+					palette: 0,
+					case: 0,
+					typing: false,
+					dragging: None,
+					dirty: true,
+					status: String::new(),
+				});
+				next_state.set(GameState::Editor);
+			}
+		}
+		GameState::Editor => {
+			// Play the edited level straight away
+			if let (Some(editor), Some(handle)) = (editor, registry.handle(level.0)) {
+				level_assets.insert(handle, editor.level.clone());
+			}
+			commands.remove_resource::<EditorState>();
+			ev_w_level.send(LevelSelectedEvent{level: level.0, edited: true});
+			next_state.set(GameState::Game);
+		}
+		_ => (),
+	}
+}
+
+// One line per editor action, named after whatever it's currently bound to
+fn editor_controls(
+	bindings: &ActionBindings,
+) -> String {
+	let bound = |action: Action| {
+		let names: Vec<String> = bindings.get(action).iter().map(|binding| binding.get_name()).collect();
+		if names.is_empty() {"-".to_string()} else {names.join(" / ")}
+	};
+	let mut controls = String::new();
+	for action in EDITOR_ACTIONS {
+		match action {
+			// All the cases share a line
+			Action::EditorCase(0) => {
+				let keys: Vec<String> = (0..MAX_CASES).map(|case| bound(Action::EditorCase(case))).collect();
+				controls.push_str(&format!("\n{}: select case", keys.join(", ")));
+			}
+			Action::EditorCase(_) => (),
+			_ => controls.push_str(&format!("\n{}: {}", bound(action), action.get_name().to_lowercase())),
+		}
+	}
+	controls
+}
+
+fn spawn_editor_ui(
+	mut commands: Commands,
+	mut solve_text_query: Query<(&mut Transform, With<SolveText>)>,
+	mut fail_text_query: Query<(&mut Transform, (With<FailText>, Without<SolveText>))>,
+	asset_server: Res<AssetServer>,
+	bindings: Res<ActionBindings>,
+) {
+	for (mut solve_pos, _) in solve_text_query.iter_mut() {
+		solve_pos.translation.z = -10.0;
+	}
+	for (mut fail_pos, _) in fail_text_query.iter_mut() {
+		fail_pos.translation.z = -10.0;
+	}
+
+	let size = Vec2::new(380.0, 880.0);
+	let margin = 15.0;
+	commands.spawn((SpriteBundle{
+		transform: Transform::from_xyz(600.0, 0.0, 950.0),
+		sprite: Sprite {
+			custom_size: Some(size),
+			color: Color::rgba(0.0, 0.0, 0.0, 0.95),
+			..default()
+		},
+		..default()
+		},
+		EditorOnly,
+	)).with_children(|parent| {
+		parent.spawn((Text2dBundle{
+			text_2d_bounds: bevy::text::Text2dBounds{ size: Vec2::new(
+				size.x - margin * 2.0,
+				size.y - margin * 2.0,
+			)},
+			transform: Transform::from_xyz(-size.x/2.0 + margin, size.y/2.0 - margin, 5.0),
+			text_anchor: bevy::sprite::Anchor::TopLeft,
+			text: Text::from_sections([
+				TextSection::new(
+				"Level Editor\n".to_string(),
+				TextStyle {
+					font: asset_server.load("fonts/XTypewriterBold.ttf"),
+					font_size: 40.0,
+					color: Color::rgb(0.9, 0.7, 0.7),
+				}),
+				TextSection::new(
+				String::new(),
+				TextStyle {
+					font: asset_server.load("fonts/XTypewriter.ttf"),
+					font_size: 22.0,
+					color: Color::rgb(0.9, 0.9, 0.9),
+				}),
+				TextSection::new(
+				editor_controls(&bindings),
+				TextStyle {
+					font: asset_server.load("fonts/XTypewriter.ttf"),
+					font_size: 18.0,
+					color: Color::rgb(0.7, 0.7, 0.7),
+				}),
+			]).with_alignment(TextAlignment::Left),
+			..default()
+			},
+			EditorPanelText,
+		));
+	});
+
+	commands.spawn((SpriteBundle{
+		transform: Transform::from_xyz(0.0, 0.0, 940.0),
+		texture: asset_server.load(PALETTE[0].get_path()),
+		sprite: Sprite {
+			custom_size: Some(Vec2::new(CELL_SIZE*4.0, CELL_SIZE*4.0)),
+			color: Color::rgba(1.0, 1.0, 1.0, 0.5),
+			..default()
+		},
+		..default()
+		},
+		EditorGhost(0),
+		RenderLayers::default(),
+		EditorOnly,
+	));
+}
+
+fn despawn_editor_ui(
+	mut commands: Commands,
+	editor_query: Query<(Entity, With<EditorOnly>)>,
+) {
+	for (entity, _) in editor_query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
+
+fn cursor_world_pos(
+	window_query: &Query<&Window, With<PrimaryWindow>>,
+	camera_query: &Query<(&Camera, &GlobalTransform), With<ViewCamera>>,
+) -> Option<Vec2> {
+	let window = window_query.get_single().ok()?;
+	let (camera, camera_pos) = camera_query.get_single().ok()?;
+	window.cursor_position().and_then(|cursor_pos| camera.viewport_to_world_2d(camera_pos, cursor_pos))
+}
+
+// Topmost tile with a cell under the cursor
+fn hovered_tile(
+	level: &LevelAsset,
+	cursor_pos: Vec2,
+) -> Option<usize> {
+	level.tiles.iter().rposition(|tile_def| {
+		tile_def.cell_locations(&level.board).iter().any(|loc|
+			(cursor_pos.x - loc.x).abs() < H_CELL_SIZE && (cursor_pos.y - loc.y).abs() < H_CELL_SIZE)
+	})
+}
+
+// Snap a tile centre to the board grid, or to a loose position on the workbench
+fn placement_at(
+	board: &BoardConfig,
+	center: Vec2,
+) -> TilePlacement {
+	if board.contains(center) {
+		let corner = board.top_left();
+		TilePlacement::Board(
+			((center.x - corner.x)/CELL_SIZE - 2.0).round().max(0.0) as usize,
+			((corner.y - center.y)/CELL_SIZE - 2.0).round().max(0.0) as usize,
+		)
+	} else {
+		TilePlacement::Bench((center.x/10.0).round() * 10.0, (center.y/10.0).round() * 10.0)
+	}
+}
+
+fn edit_board(
+	mut editor: ResMut<EditorState>,
+	mut tile_query: Query<&mut Transform, With<Tile>>,
+	window_query: Query<&Window, With<PrimaryWindow>>,
+	camera_query: Query<(&Camera, &GlobalTransform), With<ViewCamera>>,
+	actions: Res<Actions>,
+) {
+	let editor = &mut *editor;
+	if editor.typing {return;};
+
+	if actions.just_pressed(Action::EditorNextTile) {
+		editor.palette = (editor.palette + 1) % PALETTE.len();
+	}
+	if actions.just_pressed(Action::EditorPrevTile) {
+		editor.palette = (editor.palette + PALETTE.len() - 1) % PALETTE.len();
+	}
+	for case in 0..editor.level.cases.len() {
+		if actions.just_pressed(Action::EditorCase(case)) {
+			editor.case = case;
+			editor.dirty = true;
+		}
+	}
+	if actions.just_pressed(Action::EditorReport) && editor.case < editor.level.cases.len() {
+		editor.typing = true;
+		return;
+	}
+	if actions.just_pressed(Action::EditorAddCase) && editor.level.cases.len() < MAX_CASES {
+		editor.level.cases.push(CaseDef {
+			report: String::new(),
+			solution: Vec::new(),
+		});
+		editor.case = editor.level.cases.len() - 1;
+		editor.dirty = true;
+	}
+	if actions.just_pressed(Action::EditorBlocking) {
+		editor.level.threads_block_tiles = !editor.level.threads_block_tiles;
+		editor.dirty = true;
+	}
+	if actions.just_pressed(Action::EditorRemoveCase) && editor.level.cases.len() > 1 {
+		remove_case(&mut editor.level, editor.case);
+		editor.case = editor.case.min(editor.level.cases.len() - 1);
+		editor.dirty = true;
+	}
+
+	let Some(cursor_pos) = cursor_world_pos(&window_query, &camera_query) else {return};
+
+	// Dragged tiles follow the cursor until dropped
+	if let Some((index, offset)) = editor.dragging {
+		if let Ok(mut tile_pos) = tile_query.get_mut(editor.tiles[index]) {
+			tile_pos.translation.x = cursor_pos.x + offset.x;
+			tile_pos.translation.y = cursor_pos.y + offset.y;
+		}
+		if actions.just_released(Action::EditorPlace) {
+			editor.level.tiles[index].at = placement_at(&editor.level.board, cursor_pos + offset);
+			editor.dragging = None;
+			editor.dirty = true;
+		}
+		return;
+	}
+
+	let hovered = hovered_tile(&editor.level, cursor_pos);
+	if actions.just_pressed(Action::EditorPlace) {
+		match hovered {
+			Some(index) => {
+				let offset = editor.level.tiles[index].location(&editor.level.board).xy() - cursor_pos;
+				editor.dragging = Some((index, offset));
+			}
+			None => {
+				// Line the first cell of the new tile up with the cursor
+				let tile_type = PALETTE[editor.palette];
+				let first = tile_type.get_layout()[0];
+				editor.level.tiles.push(TileDef {
+					tile: tile_type,
+					at: placement_at(&editor.level.board, cursor_pos - TILE_OFFSETS[first.0][first.1]),
+					rotation: 0,
+					mirrored: false,
+					locked: false,
+					group: None,
+					tack: 0,
+					links: -1,
+				});
+				editor.dirty = true;
+			}
+		}
+		return;
+	}
+
+	let Some(index) = hovered else {return};
+	if actions.just_pressed(Action::EditorDelete) {
+		editor.level.tiles.remove(index);
+		editor.dirty = true;
+		return;
+	}
+	let case = editor.case;
+	let tile_def = &mut editor.level.tiles[index];
+	let mut changed = true;
+	if actions.just_pressed(Action::EditorRotate) {
+		tile_def.rotation = (tile_def.rotation + 1) % 4;
+	} else if actions.just_pressed(Action::EditorMirror) {
+		tile_def.mirrored = !tile_def.mirrored;
+	} else if actions.just_pressed(Action::EditorLock) {
+		tile_def.locked = !tile_def.locked;
+	} else if actions.just_pressed(Action::EditorTack) {
+		tile_def.tack = if tile_def.tack + 1 < tile_def.tile.get_layout().len() {tile_def.tack + 1} else {0};
+	} else if actions.just_pressed(Action::EditorGroup) {
+		tile_def.group = match tile_def.group {
+			None => Some(0),
+			Some(group) if group < JUNK_GROUP => Some(group + 1),
+			Some(_) => None,
+		};
+	} else if actions.just_pressed(Action::EditorLinks) {
+		tile_def.links = if tile_def.links < 9 {tile_def.links.max(0) + 1} else {-1};
+	} else if actions.just_pressed(Action::EditorSolution) {
+		let tile_type = tile_def.tile;
+		if let Some(case_def) = editor.level.cases.get_mut(case) {
+			match case_def.solution.iter().position(|required| *required == tile_type) {
+				Some(position) => {case_def.solution.remove(position);},
+				None => case_def.solution.push(tile_type),
+			}
+		}
+	} else {
+		changed = false;
+	}
+	if changed {
+		editor.dirty = true;
+	}
+}
+
+// Victims and suspects of later cases move down one, the removed case's are dropped
+fn renumber_tile(
+	tile_type: TileType,
+	case: usize,
+) -> Option<TileType> {
+	match tile_type {
+		TileType::V(i) | TileType::C(i) if i == case => None,
+		TileType::V(i) if i > case => Some(TileType::V(i - 1)),
+		TileType::C(i) if i > case => Some(TileType::C(i - 1)),
+		tile_type => Some(tile_type),
+	}
+}
+
+// Drop the case with its victim and suspect, moving later cases down to fill the gap
+fn remove_case(
+	level: &mut LevelAsset,
+	case: usize,
+) {
+	level.cases.remove(case);
+	for case_def in level.cases.iter_mut() {
+		case_def.solution = case_def.solution.iter().filter_map(|tile_type| renumber_tile(*tile_type, case)).collect();
+	}
+	level.tiles.retain(|tile_def| renumber_tile(tile_def.tile, case).is_some());
+	for tile_def in level.tiles.iter_mut() {
+		tile_def.tile = renumber_tile(tile_def.tile, case).unwrap();
+		tile_def.group = match tile_def.group {
+			Some(group) if group == case => None,
+			Some(group) if group > case && group < MAX_CASES => Some(group - 1),
+			group => group,
+		};
+	}
+}
+
+fn type_case_report(
+	mut editor: ResMut<EditorState>,
+	mut ev_r_char: EventReader<ReceivedCharacter>,
+	mut actions: ResMut<Actions>,
+	keyboard: Res<Input<KeyCode>>,
+) {
+	if !editor.typing {
+		ev_r_char.clear();
+		return;
+	}
+	let case = editor.case;
+	let Some(case_def) = editor.level.cases.get_mut(case) else {return};
+	for ev in ev_r_char.read() {
+		if !ev.char.is_control() {
+			case_def.report.push(ev.char);
+		}
+	}
+	if keyboard.just_pressed(KeyCode::Back) {
+		case_def.report.pop();
+	}
+	if keyboard.just_pressed(KeyCode::Return) || keyboard.just_pressed(KeyCode::Escape) {
+		// Stop the same press from starting to type again
+		actions.clear_just_pressed(Action::EditorReport);
+		editor.typing = false;
+	}
+}
+
+fn save_level(
+	mut editor: ResMut<EditorState>,
+	mut pkv: ResMut<PkvStore>,
+	actions: Res<Actions>,
+	registry: Res<LevelRegistry>,
+	level: Res<Level>,
+) {
+	if !actions.just_pressed(Action::EditorSave) || editor.typing {return;};
+	let Some(path) = registry.path(level.0) else {return};
+
+	let pretty = ron::ser::PrettyConfig::default()
+		.depth_limit(2)
+		.indentor("\t".to_string());
+	editor.status = match ron::ser::to_string_pretty(&editor.level, pretty) {
+		Ok(contents) => write_level_file(&path, contents),
+		Err(err) => format!("Could not save level: {}", err),
+	};
+	// Saved boards are keyed by tile order, which the edit may have changed
+	clear_board(&mut pkv, &registry, level.0);
+}
+
+// Written into the folder the game loads assets from, not wherever it was launched from
+#[cfg(not(target_arch = "wasm32"))]
+fn write_level_file(
+	path: &str,
+	contents: String,
+) -> String {
+	let path = bevy::asset::io::file::FileAssetReader::get_base_path()
+		.join(AssetPlugin::default().file_path)
+		.join(path);
+	match std::fs::write(&path, contents) {
+		Ok(_) => format!("Saved to {}", path.display()),
+		Err(err) => format!("Could not save level to {}: {}", path.display(), err),
+	}
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_level_file(
+	_path: &str,
+	_contents: String,
+) -> String {
+	"Saving levels isn't supported in the browser".to_string()
+}
+
+fn respawn_board(
+	mut commands: Commands,
+	mut editor: ResMut<EditorState>,
+	asset_server: Res<AssetServer>,
+	remove_on_reset: Query<(Entity, &RemoveOnReset)>,
+) {
+	if !editor.dirty {return;};
+	editor.dirty = false;
+	for (entity, _) in remove_on_reset.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+	editor.tiles = spawn_level_tiles(&mut commands, &asset_server, &editor.level);
+
+	// Label tiles with the settings that aren't visible on the board
+	let solution = editor.level.cases.get(editor.case).map(|case_def| case_def.solution.clone()).unwrap_or_default();
+	for tile_def in editor.level.tiles.iter() {
+		let mut label = Vec::new();
+		if let Some(group) = tile_def.group {
+			label.push(format!("G{}", group));
+		}
+		if tile_def.links != -1 {
+			label.push(format!("L{}", tile_def.links));
+		}
+		if solution.contains(&tile_def.tile) {
+			label.push("S".to_string());
+		}
+		if label.is_empty() {continue;};
+		let loc = tile_def.cell_locations(&editor.level.board)[0];
+		commands.spawn((Text2dBundle{
+			transform: Transform::from_xyz(loc.x, loc.y, 900.0),
+			text_anchor: bevy::sprite::Anchor::Center,
+			text: Text::from_section(
+				label.join(" "),
+				TextStyle {
+					font: asset_server.load("fonts/XTypewriterBold.ttf"),
+					font_size: 20.0,
+					color: Color::rgb(1.0, 1.0, 0.4),
+				}).with_alignment(TextAlignment::Center),
+			..default()
+			},
+			editor.level.board.layer_at(loc),
+			RemoveOnReset,
+		));
+	}
+}
+
+fn update_ghost(
+	mut ghost_query: Query<(&mut Transform, &mut Handle<Image>, &mut Visibility, &mut RenderLayers, &mut EditorGhost)>,
+	editor: Res<EditorState>,
+	asset_server: Res<AssetServer>,
+	window_query: Query<&Window, With<PrimaryWindow>>,
+	camera_query: Query<(&Camera, &GlobalTransform), With<ViewCamera>>,
+) {
+	let cursor_pos = cursor_world_pos(&window_query, &camera_query);
+	for (mut ghost_pos, mut texture, mut visibility, mut layers, mut ghost) in ghost_query.iter_mut() {
+		if ghost.0 != editor.palette {
+			ghost.0 = editor.palette;
+			*texture = asset_server.load(PALETTE[editor.palette].get_path());
+		}
+		let Some(cursor_pos) = cursor_pos else {
+			*visibility = Visibility::Hidden;
+			continue;
+		};
+		// Only preview a new tile when a click would place one
+		*visibility = if editor.dragging.is_none() && hovered_tile(&editor.level, cursor_pos).is_none() {
+			Visibility::Visible
+		} else {
+			Visibility::Hidden
+		};
+		let first = PALETTE[editor.palette].get_layout()[0];
+		let center = match placement_at(&editor.level.board, cursor_pos - TILE_OFFSETS[first.0][first.1]) {
+			TilePlacement::Board(x, y) => editor.level.board.index_to_grid([(x, y)].to_vec())[0].xy(),
+			TilePlacement::Bench(x, y) => Vec2::new(x, y),
+		};
+		ghost_pos.translation.x = center.x;
+		ghost_pos.translation.y = center.y;
+		*layers = editor.level.board.layer_at(cursor_pos);
+	}
+}
+
+fn update_editor_panel(
+	mut panel_query: Query<(&mut Text, With<EditorPanelText>)>,
+	editor: Res<EditorState>,
+	window_query: Query<&Window, With<PrimaryWindow>>,
+	camera_query: Query<(&Camera, &GlobalTransform), With<ViewCamera>>,
+) {
+	let mut info = format!("\nTile: {:?}\nThreads block tiles: {}\n", PALETTE[editor.palette],
+		if editor.level.threads_block_tiles {"yes"} else {"no"});
+	let hovered = cursor_world_pos(&window_query, &camera_query).and_then(|cursor_pos| hovered_tile(&editor.level, cursor_pos));
+	if let Some(index) = hovered {
+		let tile_def = &editor.level.tiles[index];
+		info.push_str(&format!("Hovered: {:?} rot {}{}{} tack {} group {} link {}\n",
+			tile_def.tile, tile_def.rotation, if tile_def.mirrored {" mirrored"} else {""}, if tile_def.locked {" locked"} else {""},
+			tile_def.tack, tile_def.group(), tile_def.links));
+	} else {
+		info.push_str("Hovered: none\n");
+	}
+	if let Some(case_def) = editor.level.cases.get(editor.case) {
+		info.push_str(&format!("\nCase {} of {}\nSolution: {:?}\n{}{}\n",
+			editor.case + 1, editor.level.cases.len(), case_def.solution,
+			case_def.report, if editor.typing {"_"} else {""}));
+	}
+	if !editor.status.is_empty() {
+		info.push_str(&format!("\n{}\n", editor.status));
+	}
+	for (mut text, _) in panel_query.iter_mut() {
+		text.sections[1].value = info.clone();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tile_def(
+		tile: TileType,
+		group: Option<usize>,
+	) -> TileDef {
+		TileDef {
+			tile: tile,
+			at: TilePlacement::Bench(0.0, 0.0),
+			rotation: 0,
+			mirrored: false,
+			locked: false,
+			group: group,
+			tack: 0,
+			links: -1,
+		}
+	}
+
+	#[test]
+	fn removing_a_case_moves_the_later_cases_down() {
+		let mut level = LevelAsset {
+			cases: [
+				[TileType::C(0), TileType::I].to_vec(),
+				[TileType::C(1), TileType::J].to_vec(),
+				// Accuses the removed case's suspect as well as its own
+				[TileType::C(2), TileType::C(0), TileType::L].to_vec(),
+			].into_iter().map(|solution| CaseDef {report: String::new(), solution: solution}).collect(),
+			tiles: [
+				tile_def(TileType::V(0), None), tile_def(TileType::V(1), None), tile_def(TileType::V(2), None),
+				tile_def(TileType::C(0), None), tile_def(TileType::C(1), None), tile_def(TileType::C(2), None),
+				tile_def(TileType::I, Some(0)), tile_def(TileType::J, Some(1)), tile_def(TileType::L, Some(2)),
+				tile_def(TileType::W(1), Some(JUNK_GROUP)),
+			].to_vec(),
+			board: BoardConfig::default(),
+			threads_block_tiles: false,
+		};
+		remove_case(&mut level, 0);
+		let solutions: Vec<Vec<TileType>> = level.cases.iter().map(|case_def| case_def.solution.clone()).collect();
+		assert_eq!(solutions, [[TileType::C(0), TileType::J].to_vec(), [TileType::C(1), TileType::L].to_vec()].to_vec());
+		let tiles: Vec<(TileType, Option<usize>)> = level.tiles.iter().map(|tile_def| (tile_def.tile, tile_def.group)).collect();
+		assert_eq!(tiles, [
+			(TileType::V(0), None), (TileType::V(1), None), (TileType::C(0), None), (TileType::C(1), None),
+			(TileType::I, None), (TileType::J, Some(0)), (TileType::L, Some(1)), (TileType::W(1), Some(JUNK_GROUP)),
+		].to_vec());
+		assert!(level.validate().is_ok());
+	}
+}
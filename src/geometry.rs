@@ -0,0 +1,290 @@
+// Geometry module with the segment tests used for thread and tile collisions
+use bevy::prelude::*;
+
+// Gaps smaller than this count as touching
+pub const EPSILON: f32 = 1e-3;
+
+// Sine of the angle below which two segments count as parallel
+const PARALLEL: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+	pub start: Vec2,
+	pub end: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Intersection {
+	None,
+	Point(Vec2),
+	// Collinear segments lying along each other
+	Overlap(Segment),
+}
+
+impl Segment {
+	pub fn new(start: Vec2, end: Vec2) -> Self {
+		Self {
+			start: start,
+			end: end,
+		}
+	}
+
+	pub fn direction(&self) -> Vec2 {
+		self.end - self.start
+	}
+
+	pub fn midpoint(&self) -> Vec2 {
+		(self.start + self.end) / 2.0
+	}
+
+	pub fn is_point(&self) -> bool {
+		self.direction().length_squared() <= EPSILON * EPSILON
+	}
+
+	pub fn closest_point(&self, point: Vec2) -> Vec2 {
+		if self.is_point() {return self.start;};
+		let t = (point - self.start).dot(self.direction()) / self.direction().length_squared();
+		self.start + self.direction() * t.clamp(0.0, 1.0)
+	}
+
+	pub fn contains_point(&self, point: Vec2) -> bool {
+		self.closest_point(point).distance_squared(point) <= EPSILON * EPSILON
+	}
+
+	pub fn has_endpoint(&self, point: Vec2) -> bool {
+		self.start.distance_squared(point) <= EPSILON * EPSILON
+		|| self.end.distance_squared(point) <= EPSILON * EPSILON
+	}
+
+	// Segments that only meet where they both end, like threads chained through a tack
+	pub fn meets_at_endpoint(&self, other: &Segment, point: Vec2) -> bool {
+		self.has_endpoint(point) && other.has_endpoint(point)
+	}
+
+	pub fn intersection(&self, other: &Segment) -> Intersection {
+		// Zero length segments are just points
+		if self.is_point() {
+			return if other.contains_point(self.start) {Intersection::Point(self.start)} else {Intersection::None};
+		}
+		if other.is_point() {
+			return if self.contains_point(other.start) {Intersection::Point(other.start)} else {Intersection::None};
+		}
+
+		let r = self.direction();
+		let s = other.direction();
+		let offset = other.start - self.start;
+		let denom = r.perp_dot(s);
+
+		// Both ends of the other segment on this one's line, so they can only lie along each other
+		let on_line = |point: Vec2| (point - self.start).perp_dot(r).abs() <= EPSILON * r.length();
+		if on_line(other.start) && on_line(other.end) {
+			let r_length_squared = r.length_squared();
+			let t0 = offset.dot(r) / r_length_squared;
+			let t1 = t0 + s.dot(r) / r_length_squared;
+			let low = t0.min(t1).max(0.0);
+			let high = t0.max(t1).min(1.0);
+			let tolerance = EPSILON / r.length();
+			if low > high + tolerance {
+				return Intersection::None;
+			}
+			if high - low <= tolerance {
+				return Intersection::Point(self.start + r * low.min(high));
+			}
+			return Intersection::Overlap(Segment::new(self.start + r * low, self.start + r * high));
+		}
+		// Only truly parallel lines skip the crossing test, nearly parallel ones can still meet at an end
+		if denom.abs() <= PARALLEL * r.length() * s.length() {
+			return Intersection::None;
+		}
+
+		let t = offset.perp_dot(s) / denom;
+		let u = offset.perp_dot(r) / denom;
+		let t_tolerance = EPSILON / r.length();
+		let u_tolerance = EPSILON / s.length();
+		if t < -t_tolerance || t > 1.0 + t_tolerance || u < -u_tolerance || u > 1.0 + u_tolerance {
+			return Intersection::None;
+		}
+		Intersection::Point(self.start + r * t.clamp(0.0, 1.0))
+	}
+
+	pub fn intersects(&self, other: &Segment) -> bool {
+		self.intersection(other) != Intersection::None
+	}
+
+	pub fn intersects_rect(&self, rect: Rect) -> bool {
+		let inflated = rect.inset(EPSILON);
+		if inflated.contains(self.start) || inflated.contains(self.end) {return true;};
+		let corners = [
+			Vec2::new(rect.min.x, rect.min.y), Vec2::new(rect.max.x, rect.min.y),
+			Vec2::new(rect.max.x, rect.max.y), Vec2::new(rect.min.x, rect.max.y),
+		];
+		(0..4).any(|i| self.intersects(&Segment::new(corners[i], corners[(i + 1) % 4])))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::{rngs::StdRng, Rng};
+	use crate::testing::check_cases;
+
+	// Half cell steps like tack positions, so touching cases come up often
+	fn random_point(
+		rng: &mut StdRng,
+	) -> Vec2 {
+		Vec2::new(rng.gen_range(-20..=20) as f32, rng.gen_range(-20..=20) as f32) * 20.0
+	}
+
+	fn random_segment(
+		rng: &mut StdRng,
+	) -> Segment {
+		Segment::new(random_point(rng), random_point(rng))
+	}
+
+	fn random_direction(
+		rng: &mut StdRng,
+	) -> Vec2 {
+		Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU))
+	}
+
+	#[test]
+	fn intersection_is_symmetric() {
+		check_cases(14, |rng| {
+			let a = random_segment(rng);
+			let b = random_segment(rng);
+			assert_eq!(a.intersects(&b), b.intersects(&a), "{:?} {:?}", a, b);
+			assert_eq!(a.intersects(&b), a.intersects(&Segment::new(b.end, b.start)), "{:?} {:?}", a, b);
+		});
+	}
+
+	#[test]
+	fn intersection_point_lies_on_both_segments() {
+		check_cases(15, |rng| {
+			let a = random_segment(rng);
+			let b = random_segment(rng);
+			if let Intersection::Point(point) = a.intersection(&b) {
+				// Slack for the distance the tolerance lets a crossing sit past an end
+				assert!(a.closest_point(point).distance(point) < 0.01, "{:?} {:?} {:?}", a, b, point);
+				assert!(b.closest_point(point).distance(point) < 0.01, "{:?} {:?} {:?}", a, b, point);
+			}
+		});
+	}
+
+	#[test]
+	fn shared_endpoints_touch() {
+		check_cases(16, |rng| {
+			let shared = random_point(rng);
+			let a = Segment::new(shared, shared + random_direction(rng) * rng.gen_range(1.0..400.0));
+			let b = Segment::new(random_point(rng), shared);
+			if b.is_point() {return;};
+			assert!(a.intersects(&b) && b.intersects(&a), "{:?} {:?}", a, b);
+			assert!(a.meets_at_endpoint(&b, shared));
+			if let Intersection::Point(point) = a.intersection(&b) {
+				assert!(point.distance(shared) < 0.01, "{:?} {:?} {:?}", a, b, point);
+			}
+		});
+	}
+
+	#[test]
+	fn collinear_segments_overlap_only_where_they_share_the_line() {
+		check_cases(17, |rng| {
+			let origin = random_point(rng);
+			let direction = random_direction(rng);
+			let along = |t: f32| origin + direction * t;
+			let (a0, a1) = (rng.gen_range(0.0..100.0_f32), rng.gen_range(150.0..300.0_f32));
+			let a = Segment::new(along(a0), along(a1));
+
+			// Overlapping stretch of the same line
+			let (b0, b1) = (rng.gen_range(a0 + 1.0..a1 - 1.0), rng.gen_range(a1 + 1.0..400.0));
+			match a.intersection(&Segment::new(along(b0), along(b1))) {
+				Intersection::Overlap(overlap) => {
+					assert!(overlap.start.distance(along(b0)) < 0.01 && overlap.end.distance(along(a1)) < 0.01, "{:?}", overlap);
+				}
+				other => panic!("expected overlap, got {:?}", other),
+			}
+
+			// Further along the same line with a gap
+			let gap = Segment::new(along(a1 + 1.0), along(a1 + 50.0));
+			assert_eq!(a.intersection(&gap), Intersection::None);
+
+			// End to end, meeting in a single point
+			let touching = Segment::new(along(a1), along(a1 + 50.0));
+			assert!(matches!(a.intersection(&touching), Intersection::Point(point) if point.distance(along(a1)) < 0.01));
+
+			// Parallel but off the line
+			let offset = Segment::new(a.start + direction.perp(), a.end + direction.perp());
+			assert_eq!(a.intersection(&offset), Intersection::None);
+		});
+	}
+
+	#[test]
+	fn gaps_inside_epsilon_count_as_touching() {
+		check_cases(18, |rng| {
+			let start = random_point(rng);
+			let direction = random_direction(rng);
+			let a = Segment::new(start, start + direction * rng.gen_range(10.0..400.0));
+			// Crossing segment that stops just short of, or well short of, the first one
+			let normal = direction.perp();
+			let hit = a.start + a.direction() * rng.gen_range(0.1..0.9);
+			let near = Segment::new(hit + normal * EPSILON * 0.5, hit + normal * 100.0);
+			let far = Segment::new(hit + normal * EPSILON * 10.0, hit + normal * 100.0);
+			assert!(a.intersects(&near) && near.intersects(&a), "{:?} {:?}", a, near);
+			assert!(!a.intersects(&far) && !far.intersects(&a), "{:?} {:?}", a, far);
+		});
+	}
+
+	#[test]
+	fn points_only_touch_segments_they_lie_on() {
+		check_cases(19, |rng| {
+			let a = random_segment(rng);
+			if a.is_point() {return;};
+			let on = a.start + a.direction() * rng.gen_range(0.0..=1.0);
+			let off = on + a.direction().normalize().perp() * 1.0;
+			assert!(a.intersects(&Segment::new(on, on)));
+			assert!(!a.intersects(&Segment::new(off, off)));
+		});
+	}
+
+	fn random_rect(
+		rng: &mut StdRng,
+	) -> Rect {
+		let corner = random_point(rng);
+		Rect::from_corners(corner, corner + Vec2::new(rng.gen_range(1..=4) as f32, rng.gen_range(1..=4) as f32) * 20.0)
+	}
+
+	#[test]
+	fn segments_hit_rects_they_pass_through() {
+		check_cases(20, |rng| {
+			let rect = random_rect(rng);
+			let a = random_segment(rng);
+			assert_eq!(a.intersects_rect(rect), Segment::new(a.end, a.start).intersects_rect(rect), "{:?} {:?}", a, rect);
+			// Any point of the segment well inside the rect means they meet
+			let inside = (0..=100).map(|i| a.start + a.direction() * (i as f32 / 100.0)).any(|point| rect.inset(-0.1).contains(point));
+			if inside {
+				assert!(a.intersects_rect(rect), "{:?} {:?}", a, rect);
+			}
+			// Through the middle from outside on either side
+			let direction = random_direction(rng);
+			let through = Segment::new(rect.center() - direction * 200.0, rect.center() + direction * 200.0);
+			assert!(through.intersects_rect(rect), "{:?} {:?}", through, rect);
+		});
+	}
+
+	#[test]
+	fn segments_miss_rects_off_to_one_side() {
+		check_cases(21, |rng| {
+			let rect = random_rect(rng);
+			let a = random_segment(rng);
+			let gap = EPSILON * 10.0;
+			let clear = (a.start.x.max(a.end.x) < rect.min.x - gap) || (a.start.x.min(a.end.x) > rect.max.x + gap)
+				|| (a.start.y.max(a.end.y) < rect.min.y - gap) || (a.start.y.min(a.end.y) > rect.max.y + gap);
+			if clear {
+				assert!(!a.intersects_rect(rect), "{:?} {:?}", a, rect);
+			}
+			// Running along an edge counts as touching, just past it doesn't
+			let along = Segment::new(Vec2::new(rect.min.x - 40.0, rect.max.y), Vec2::new(rect.max.x + 40.0, rect.max.y));
+			let above = Segment::new(along.start + Vec2::Y * gap, along.end + Vec2::Y * gap);
+			assert!(along.intersects_rect(rect) && !above.intersects_rect(rect), "{:?}", rect);
+		});
+	}
+}
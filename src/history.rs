@@ -0,0 +1,453 @@
+// History module for undoing and redoing board actions
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{derivables::*, threads::spawn_thread};
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_resource::<History>()
+			.add_systems(Update, (
+				history_shortcuts,
+				step_history,
+			).chain().run_if(in_state(GameState::Game)))
+			// Record once the action's commands have been applied
+			.add_systems(PostUpdate, (
+				record_history,
+			).run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+// Everything the player can change on the board, keyed by TileId so it survives a restart
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoardSnapshot {
+	pub tiles: Vec<TileSnapshot>,
+	pub threads: Vec<ThreadSnapshot>,
+	pub thread_count: Vec<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileSnapshot {
+	pub id: usize,
+	pub translation: Vec3,
+	#[serde(default)]
+	pub rotation: u8,
+	#[serde(default)]
+	pub mirrored: bool,
+	pub tack: Option<TackSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TackSnapshot {
+	pub group: usize,
+	pub end: bool,
+	pub used: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThreadSnapshot {
+	pub group: usize,
+	pub index: f32,
+	// TileIds of the tiles holding each tack
+	pub tacks: Vec<usize>,
+}
+
+impl BoardSnapshot {
+	// Snapshots are sorted by TileId, so this is a straight walk down both
+	pub fn same_tiles(&self, other: &BoardSnapshot) -> bool {
+		self.tiles.iter().map(|tile| tile.id).eq(other.tiles.iter().map(|tile| tile.id))
+	}
+}
+
+// One board action as the tiles and threads it changed, so it can be played forwards or backwards.
+// Worked out by comparing snapshots of the board taken either side of the action
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoardCommand {
+	pub action: BoardAction,
+	// Each tile the action touched, before and after
+	pub tiles: Vec<(TileSnapshot, TileSnapshot)>,
+	pub removed_threads: Vec<ThreadSnapshot>,
+	pub added_threads: Vec<ThreadSnapshot>,
+	pub thread_count: (Vec<f32>, Vec<f32>),
+}
+
+impl BoardCommand {
+	// None if the action left the board as it was, both snapshots have to hold the same tiles
+	pub fn between(
+		action: BoardAction,
+		before: &BoardSnapshot,
+		after: &BoardSnapshot,
+	) -> Option<Self> {
+		debug_assert!(before.same_tiles(after));
+		let tiles: Vec<(TileSnapshot, TileSnapshot)> = before.tiles.iter().zip(after.tiles.iter())
+			.filter(|(old, tile)| old != tile)
+			.map(|(old, tile)| (old.clone(), tile.clone()))
+			.collect();
+		// A group only has one thread at each index
+		let key = |thread: &ThreadSnapshot| (thread.group, thread.index.to_bits());
+		let before_threads: HashMap<_, &ThreadSnapshot> = before.threads.iter().map(|thread| (key(thread), thread)).collect();
+		let after_threads: HashMap<_, &ThreadSnapshot> = after.threads.iter().map(|thread| (key(thread), thread)).collect();
+		let removed_threads: Vec<ThreadSnapshot> = before.threads.iter()
+			.filter(|thread| after_threads.get(&key(thread)) != Some(thread))
+			.cloned()
+			.collect();
+		let added_threads: Vec<ThreadSnapshot> = after.threads.iter()
+			.filter(|thread| before_threads.get(&key(thread)) != Some(thread))
+			.cloned()
+			.collect();
+		if tiles.is_empty() && removed_threads.is_empty() && added_threads.is_empty() && before.thread_count == after.thread_count {
+			return None;
+		}
+		Some(Self {
+			action: action,
+			tiles: tiles,
+			removed_threads: removed_threads,
+			added_threads: added_threads,
+			thread_count: (before.thread_count.clone(), after.thread_count.clone()),
+		})
+	}
+
+	pub fn apply(
+		&self,
+		commands: &mut Commands,
+		thread_count: &mut ThreadCount,
+		tile_query: &mut Query<(&TileId, &mut Transform, &mut Orientation, &Children)>,
+		tack_query: &mut Query<(Entity, &mut Tack), Without<TileId>>,
+		thread_query: &Query<(Entity, &Thread, Has<Loose>)>,
+		held_query: &Query<Entity, With<Held>>,
+	) {
+		let tiles: Vec<&TileSnapshot> = self.tiles.iter().map(|(_, after)| after).collect();
+		rewrite_board(commands, &tiles, Some(&self.removed_threads), &self.added_threads,
+			tile_query, tack_query, thread_query, held_query);
+		thread_count.0 = self.thread_count.1.clone();
+	}
+
+	pub fn revert(
+		&self,
+		commands: &mut Commands,
+		thread_count: &mut ThreadCount,
+		tile_query: &mut Query<(&TileId, &mut Transform, &mut Orientation, &Children)>,
+		tack_query: &mut Query<(Entity, &mut Tack), Without<TileId>>,
+		thread_query: &Query<(Entity, &Thread, Has<Loose>)>,
+		held_query: &Query<Entity, With<Held>>,
+	) {
+		let tiles: Vec<&TileSnapshot> = self.tiles.iter().map(|(before, _)| before).collect();
+		rewrite_board(commands, &tiles, Some(&self.added_threads), &self.removed_threads,
+			tile_query, tack_query, thread_query, held_query);
+		thread_count.0 = self.thread_count.0.clone();
+	}
+}
+
+#[derive(Resource, Default)]
+pub struct History {
+	pub commands: Vec<BoardCommand>,
+	// Commands currently played, the ones after it have been undone
+	pub cursor: usize,
+	// Board as of the last command, what the next action gets compared against
+	board: Option<BoardSnapshot>,
+}
+
+impl History {
+	pub fn record(
+		&mut self,
+		action: BoardAction,
+		snapshot: &BoardSnapshot,
+	) {
+		// Commands can only move tiles that are still there, so a board whose tiles came or went starts over
+		let rebuilt = self.board.as_ref().is_some_and(|before| !before.same_tiles(snapshot));
+		if action == BoardAction::Load || action == BoardAction::Resume || rebuilt {
+			self.commands.clear();
+			self.cursor = 0;
+		} else if let Some(before) = &self.board {
+			if let Some(command) = BoardCommand::between(action, before, snapshot) {
+				// A new action throws away anything that was undone
+				self.commands.truncate(self.cursor);
+				self.commands.push(command);
+				self.cursor = self.commands.len();
+			}
+		}
+		self.board = Some(snapshot.clone());
+	}
+}
+
+pub fn capture_snapshot(
+	tile_query: &Query<(&TileId, &Transform, &Orientation, &Children)>,
+	tack_query: &Query<(Entity, &Tack)>,
+	thread_query: &Query<(&Thread, Has<Loose>)>,
+	thread_count: &ThreadCount,
+) -> BoardSnapshot {
+	let mut thread_count = thread_count.0.clone();
+	let mut loose_tacks = Vec::new();
+	for (thread, loose) in thread_query.iter() {
+		if loose {
+			loose_tacks.push(thread.tacks[0]);
+			thread_count[thread.group] -= 1.0;
+		}
+	}
+
+	let mut tack_ids = HashMap::new();
+	let mut tiles = Vec::new();
+	for (tile_id, tile_pos, orientation, children) in tile_query.iter() {
+		let mut tack = None;
+		for &child in children.iter() {
+			if let Ok((tack_entity, tack_info)) = tack_query.get(child) {
+				tack_ids.insert(tack_entity, tile_id.0);
+				// Loose threads get cut, so store their tack as it would be once cancelled
+				let loose = loose_tacks.contains(&tack_entity);
+				tack = Some(TackSnapshot {
+					group: tack_info.group,
+					end: tack_info.end || loose,
+					used: tack_info.used && !loose,
+				});
+			}
+		}
+		tiles.push(TileSnapshot {
+			id: tile_id.0,
+			translation: tile_pos.translation,
+			rotation: orientation.rotation,
+			mirrored: orientation.mirrored,
+			tack: tack,
+		});
+	}
+	tiles.sort_by_key(|tile| tile.id);
+
+	let mut threads = Vec::new();
+	for (thread, loose) in thread_query.iter() {
+		if loose || thread.index == -1.0 {continue;};
+		threads.push(ThreadSnapshot {
+			group: thread.group,
+			index: thread.index,
+			tacks: thread.tacks.iter().filter_map(|tack| tack_ids.get(tack).copied()).collect(),
+		});
+	}
+	threads.sort_by(|a, b| (a.group, a.index).partial_cmp(&(b.group, b.index)).unwrap());
+
+	BoardSnapshot {
+		tiles: tiles,
+		threads: threads,
+		thread_count: thread_count,
+	}
+}
+
+pub fn apply_snapshot(
+	commands: &mut Commands,
+	snapshot: &BoardSnapshot,
+	thread_count: &mut ThreadCount,
+	tile_query: &mut Query<(&TileId, &mut Transform, &mut Orientation, &Children)>,
+	tack_query: &mut Query<(Entity, &mut Tack), Without<TileId>>,
+	thread_query: &Query<(Entity, &Thread, Has<Loose>)>,
+	held_query: &Query<Entity, With<Held>>,
+) {
+	let tiles: Vec<&TileSnapshot> = snapshot.tiles.iter().collect();
+	rewrite_board(commands, &tiles, None, &snapshot.threads,
+		tile_query, tack_query, thread_query, held_query);
+	thread_count.0 = snapshot.thread_count.clone();
+}
+
+// Move the given tiles back into place and swap threads out, None takes down every placed thread
+fn rewrite_board(
+	commands: &mut Commands,
+	tiles: &[&TileSnapshot],
+	remove_threads: Option<&[ThreadSnapshot]>,
+	add_threads: &[ThreadSnapshot],
+	tile_query: &mut Query<(&TileId, &mut Transform, &mut Orientation, &Children)>,
+	tack_query: &mut Query<(Entity, &mut Tack), Without<TileId>>,
+	thread_query: &Query<(Entity, &Thread, Has<Loose>)>,
+	held_query: &Query<Entity, With<Held>>,
+) {
+	for entity in held_query.iter() {
+		commands.entity(entity).remove::<Held>();
+	}
+
+	let mut tacks = HashMap::new();
+	let mut tack_ids = HashMap::new();
+	for (tile_id, mut tile_pos, mut orientation, children) in tile_query.iter_mut() {
+		let tile = tiles.iter().find(|tile| tile.id == tile_id.0);
+		if let Some(tile) = tile {
+			tile_pos.translation = tile.translation;
+			orientation.rotation = tile.rotation;
+			orientation.mirrored = tile.mirrored;
+			orientation.apply(&mut tile_pos);
+		}
+		for &child in children.iter() {
+			if let Ok((tack_entity, mut tack)) = tack_query.get_mut(child) {
+				if let Some(tack_snapshot) = tile.and_then(|tile| tile.tack.as_ref()) {
+					tack.group = tack_snapshot.group;
+					tack.end = tack_snapshot.end;
+					tack.used = tack_snapshot.used;
+				}
+				// Worked out from the orientation, the tack itself isn't moved across until the frame ends
+				tacks.insert(tile_id.0, (tack_entity, tile_pos.transform_point(orientation.offset(tack.cell).extend(0.0)).xy()));
+				tack_ids.insert(tack_entity, tile_id.0);
+			}
+		}
+	}
+
+	for (thread_entity, thread, loose) in thread_query.iter() {
+		if thread.index == -1.0 {continue;};
+		// Threads being strung get cut, the tiles already hold their tacks as if they were
+		let remove = loose || remove_threads.map_or(true, |remove_threads| remove_threads.iter().any(|removed| {
+			removed.group == thread.group && removed.index == thread.index
+				&& removed.tacks.iter().copied().eq(thread.tacks.iter().filter_map(|tack| tack_ids.get(tack).copied()))
+		}));
+		if remove {
+			commands.entity(thread_entity).despawn_recursive();
+		}
+	}
+
+	for thread in add_threads.iter() {
+		let Some(thread_tacks) = thread.tacks.iter().map(|id| tacks.get(id).copied()).collect::<Option<Vec<_>>>() else {continue};
+		spawn_thread(commands, thread.group, thread.index,
+			thread_tacks.iter().map(|(tack, _)| *tack).collect(),
+			thread_tacks.iter().map(|(_, loc)| *loc).collect());
+	}
+}
+
+fn history_shortcuts(
+	mut ev_w_undo: EventWriter<UndoEvent>,
+	mut ev_w_redo: EventWriter<RedoEvent>,
+	actions: Res<Actions>,
+) {
+	if actions.just_pressed(Action::Redo) {
+		ev_w_redo.send(RedoEvent());
+	} else if actions.just_pressed(Action::Undo) {
+		ev_w_undo.send(UndoEvent());
+	}
+}
+
+// One step a frame, threads a step spawns only exist once its commands are applied
+fn step_history(
+	mut commands: Commands,
+	mut history: ResMut<History>,
+	mut thread_count: ResMut<ThreadCount>,
+	mut ev_r_undo: EventReader<UndoEvent>,
+	mut ev_r_redo: EventReader<RedoEvent>,
+	mut tile_query: Query<(&TileId, &mut Transform, &mut Orientation, &Children)>,
+	mut tack_query: Query<(Entity, &mut Tack), Without<TileId>>,
+	thread_query: Query<(Entity, &Thread, Has<Loose>)>,
+	held_query: Query<Entity, With<Held>>,
+) {
+	let steps = ev_r_redo.read().count() as i32 - ev_r_undo.read().count() as i32;
+	if steps < 0 && history.cursor > 0 {
+		history.cursor -= 1;
+		history.commands[history.cursor].revert(&mut commands, &mut thread_count,
+			&mut tile_query, &mut tack_query, &thread_query, &held_query);
+	} else if steps > 0 && history.cursor < history.commands.len() {
+		history.commands[history.cursor].apply(&mut commands, &mut thread_count,
+			&mut tile_query, &mut tack_query, &thread_query, &held_query);
+		history.cursor += 1;
+	} else {
+		return;
+	}
+	// Compared against once the step has been applied
+	history.board = None;
+}
+
+fn record_history(
+	mut history: ResMut<History>,
+	mut ev_r_action: EventReader<BoardActionEvent>,
+	tile_query: Query<(&TileId, &Transform, &Orientation, &Children)>,
+	tack_query: Query<(Entity, &Tack)>,
+	thread_query: Query<(&Thread, Has<Loose>)>,
+	thread_count: Res<ThreadCount>,
+) {
+	let actions: Vec<BoardAction> = ev_r_action.read().map(|ev| ev.0).collect();
+	if actions.is_empty() && history.board.is_some() {return;};
+	// Only taken on frames with an action, or after an undo or redo cleared the last one
+	let snapshot = capture_snapshot(&tile_query, &tack_query, &thread_query, &thread_count);
+	for action in actions {
+		history.record(action, &snapshot);
+	}
+	history.board = Some(snapshot);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tile(
+		id: usize,
+		x: f32,
+	) -> TileSnapshot {
+		TileSnapshot {
+			id: id,
+			translation: Vec3::new(x, 0.0, 300.0),
+			rotation: 0,
+			mirrored: false,
+			tack: Some(TackSnapshot {group: NEUTRAL_GROUP, end: false, used: false}),
+		}
+	}
+
+	fn thread(
+		group: usize,
+		index: f32,
+		tacks: Vec<usize>,
+	) -> ThreadSnapshot {
+		ThreadSnapshot {
+			group: group,
+			index: index,
+			tacks: tacks,
+		}
+	}
+
+	fn board(
+		tiles: Vec<TileSnapshot>,
+		threads: Vec<ThreadSnapshot>,
+	) -> BoardSnapshot {
+		BoardSnapshot {
+			tiles: tiles,
+			threads: threads,
+			thread_count: vec![0.0; 3],
+		}
+	}
+
+	#[test]
+	fn commands_only_hold_what_changed() {
+		let before = board((0..5).map(|id| tile(id, id as f32)).collect(), vec![thread(0, 1.0, vec![0, 1])]);
+		let mut after = before.clone();
+		after.tiles[3].translation.x = 40.0;
+		after.threads.push(thread(0, 2.0, vec![1, 2]));
+		after.thread_count[0] = 2.0;
+		let command = BoardCommand::between(BoardAction::MoveTile, &before, &after).unwrap();
+		assert_eq!(command.tiles, vec![(before.tiles[3].clone(), after.tiles[3].clone())]);
+		assert!(command.removed_threads.is_empty());
+		assert_eq!(command.added_threads, vec![thread(0, 2.0, vec![1, 2])]);
+		assert!(BoardCommand::between(BoardAction::MoveTile, &before, &before).is_none());
+	}
+
+	#[test]
+	fn rethreading_an_index_swaps_the_thread() {
+		let before = board((0..3).map(|id| tile(id, id as f32)).collect(), vec![thread(1, 1.0, vec![0, 1])]);
+		let after = board(before.tiles.clone(), vec![thread(1, 1.0, vec![0, 2])]);
+		let command = BoardCommand::between(BoardAction::DrawThread, &before, &after).unwrap();
+		assert_eq!(command.removed_threads, before.threads);
+		assert_eq!(command.added_threads, after.threads);
+	}
+
+	#[test]
+	fn history_starts_over_when_tiles_come_or_go() {
+		let mut history = History::default();
+		let start = board((0..3).map(|id| tile(id, id as f32)).collect(), Vec::new());
+		history.record(BoardAction::Load, &start);
+		let mut moved = start.clone();
+		moved.tiles[0].translation.x = 80.0;
+		history.record(BoardAction::MoveTile, &moved);
+		assert_eq!((history.commands.len(), history.cursor), (1, 1));
+
+		// A tile that wasn't there before can't be put back by undoing
+		let mut grown = moved.clone();
+		grown.tiles.push(tile(3, 3.0));
+		assert!(!moved.same_tiles(&grown));
+		history.record(BoardAction::MoveTile, &grown);
+		assert!(history.commands.is_empty() && history.cursor == 0);
+		let mut shrunk = grown.clone();
+		shrunk.tiles.remove(1);
+		history.record(BoardAction::MoveTile, &shrunk);
+		assert!(history.commands.is_empty());
+		assert_eq!(history.board, Some(shrunk));
+	}
+}
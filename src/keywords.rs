@@ -0,0 +1,226 @@
+// Keywords module for linking tagged words in case reports to the evidence they describe
+use bevy::{prelude::*, text::TextLayoutInfo, window::PrimaryWindow};
+
+use crate::{derivables::*, tiles::track_hovered_tile};
+
+pub struct KeywordsPlugin;
+
+impl Plugin for KeywordsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_resource::<HoveredKeyword>()
+			.add_systems(Update, (
+				hover_keywords,
+				highlight_tiles,
+				highlight_keywords,
+			).chain().after(track_hovered_tile).run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+const REPORT_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const KEYWORD_COLOR: Color = Color::rgb(0.45, 0.1, 0.1);
+const KEYWORD_HIGHLIGHT: Color = Color::rgb(0.85, 0.2, 0.1);
+const TILE_HIGHLIGHT: Color = Color::rgb(1.0, 0.85, 0.4);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+	pub text: String,
+	pub keyword: Option<TileType>,
+}
+
+// Splits "[shown words|J]" tags out of report text, anything that isn't a valid tag is left as written
+pub fn parse_markup(
+	text: &str,
+) -> Vec<Span> {
+	let mut spans = Vec::new();
+	let mut plain = String::new();
+	let mut rest = text;
+	while let Some(open) = rest.find('[') {
+		let tag = rest[open..].find(']').and_then(|close| {
+			let inner = &rest[open + 1..open + close];
+			// A bracket opened inside means this one is stray, the tag (if any) starts later
+			if inner.contains('[') {return None};
+			let (shown, tile) = inner.rsplit_once('|')?;
+			let tile_type = ron::from_str::<TileType>(tile.trim()).ok()?;
+			Some((shown, tile_type, open + close + 1))
+		});
+		let Some((shown, tile_type, end)) = tag else {
+			plain.push_str(&rest[..open + 1]);
+			rest = &rest[open + 1..];
+			continue;
+		};
+		plain.push_str(&rest[..open]);
+		if !plain.is_empty() {
+			spans.push(Span {text: std::mem::take(&mut plain), keyword: None});
+		}
+		spans.push(Span {text: shown.to_string(), keyword: Some(tile_type)});
+		rest = &rest[end..];
+	}
+	plain.push_str(rest);
+	if !plain.is_empty() {
+		spans.push(Span {text: plain, keyword: None});
+	}
+	spans
+}
+
+// Report text sections and the evidence each one is tagged with
+pub fn report_sections(
+	asset_server: &Res<AssetServer>,
+	report: &str,
+) -> (Vec<TextSection>, Vec<Option<TileType>>) {
+	let spans = parse_markup(report);
+	let sections = spans.iter().map(|span| TextSection::new(
+		span.text.clone(),
+	TextStyle {
+		font: asset_server.load("fonts/XTypewriterBold.ttf"),
+		font_size: 28.0,
+		color: if span.keyword.is_some() {KEYWORD_COLOR} else {REPORT_COLOR},
+	})).collect();
+	(sections, spans.iter().map(|span| span.keyword).collect())
+}
+
+// Hit test the glyphs of open case reports against the cursor
+fn hover_keywords(
+	mut hovered_keyword: ResMut<HoveredKeyword>,
+	text_query: Query<(&CaseFileText, &TextLayoutInfo, &GlobalTransform, &Parent)>,
+	open_case_report_query: Query<&Transform, With<OpenCaseReport>>,
+	window_query: Query<&Window, With<PrimaryWindow>>,
+	pointer: Res<Pointer>,
+) {
+	let window = window_query.get_single().unwrap();
+	let mut keyword = None;
+	if let Some(cursor_pos) = pointer.ui {
+		let scale_factor = window.resolution.scale_factor() as f32;
+		for (case_text, layout, text_pos, parent) in text_query.iter() {
+			// Closed reports are tucked behind the board
+			let Ok(open_pos) = open_case_report_query.get(parent.get()) else {continue};
+			if open_pos.translation.z < 0.0 {continue;};
+			// Glyphs are laid out up from the bottom left, top left anchored text hangs below its origin
+			let origin = Vec2::new(0.0, -layout.logical_size.y);
+			for glyph in layout.glyphs.iter() {
+				let Some(tile_type) = case_text.keywords.get(glyph.section_index).copied().flatten() else {continue};
+				let centre = text_pos.transform_point((origin + glyph.position / scale_factor).extend(0.0)).xy();
+				// Padded so the gaps between letters still count
+				let half_size = glyph.size / scale_factor / 2.0 + 2.0;
+				if (cursor_pos - centre).abs().cmple(half_size).all() {
+					keyword = Some(tile_type);
+				}
+			}
+		}
+	}
+	if hovered_keyword.0 != keyword {
+		hovered_keyword.0 = keyword;
+	}
+}
+
+// Tint the tiles a hovered keyword refers to, putting back whatever colour they had before
+fn highlight_tiles(
+	mut commands: Commands,
+	mut tile_query: Query<(Entity, &Tile, &mut Sprite, Option<&KeywordHighlight>)>,
+	hovered_keyword: Res<HoveredKeyword>,
+) {
+	if !hovered_keyword.is_changed() {return;};
+	for (entity, tile, mut sprite, highlight) in tile_query.iter_mut() {
+		let highlighted = hovered_keyword.0 == Some(tile.0);
+		match highlight {
+			Some(highlight) if !highlighted => {
+				sprite.color = highlight.0;
+				commands.entity(entity).remove::<KeywordHighlight>();
+			},
+			None if highlighted => {
+				commands.entity(entity).insert(KeywordHighlight(sprite.color));
+				sprite.color = TILE_HIGHLIGHT;
+			},
+			_ => {},
+		}
+	}
+}
+
+fn highlight_keywords(
+	mut text_query: Query<(&CaseFileText, &mut Text)>,
+	added_query: Query<(), Added<CaseFileText>>,
+	tile_query: Query<&Tile>,
+	hovered_tile: Res<HoveredTile>,
+) {
+	if !hovered_tile.is_changed() && added_query.is_empty() {return;};
+	let tile_type = hovered_tile.0.and_then(|tile_entity| tile_query.get(tile_entity).ok()).map(|tile| tile.0);
+	for (case_text, mut text) in text_query.iter_mut() {
+		for (section, keyword) in text.sections.iter_mut().zip(case_text.keywords.iter()) {
+			let Some(keyword) = keyword else {continue};
+			let color = if Some(*keyword) == tile_type {KEYWORD_HIGHLIGHT} else {KEYWORD_COLOR};
+			if section.style.color != color {
+				section.style.color = color;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn plain(text: &str) -> Span {
+		Span {text: text.to_string(), keyword: None}
+	}
+
+	fn tagged(text: &str, tile_type: TileType) -> Span {
+		Span {text: text.to_string(), keyword: Some(tile_type)}
+	}
+
+	#[test]
+	fn plain_text_is_one_span() {
+		assert_eq!(parse_markup("No signs of murder weapon."), vec![plain("No signs of murder weapon.")]);
+		assert_eq!(parse_markup(""), vec![]);
+	}
+
+	#[test]
+	fn tags_split_out_their_tile() {
+		assert_eq!(parse_markup("Broke in, [DNA sample|I] and [fingerprints|O] acquired."), vec![
+			plain("Broke in, "),
+			tagged("DNA sample", TileType::I),
+			plain(" and "),
+			tagged("fingerprints", TileType::O),
+			plain(" acquired."),
+		]);
+		assert_eq!(parse_markup("[Victim| V(1) ]"), vec![tagged("Victim", TileType::V(1))]);
+	}
+
+	#[test]
+	fn unknown_tiles_are_left_as_written() {
+		assert_eq!(parse_markup("Found [a lot|Q] of it."), vec![plain("Found [a lot|Q] of it.")]);
+		assert_eq!(parse_markup("Found [a lot] of it."), vec![plain("Found [a lot] of it.")]);
+	}
+
+	#[test]
+	fn unbalanced_brackets_fall_back_to_plain_text() {
+		assert_eq!(parse_markup("Found [a lot|J of it."), vec![plain("Found [a lot|J of it.")]);
+		assert_eq!(parse_markup("Found a lot|J] of it."), vec![plain("Found a lot|J] of it.")]);
+		// A stray bracket doesn't swallow the text before the next tag
+		assert_eq!(parse_markup("A [stray bracket and [casings|J] found."), vec![
+			plain("A [stray bracket and "),
+			tagged("casings", TileType::J),
+			plain(" found."),
+		]);
+		assert_eq!(parse_markup("[[casings|J]|L]"), vec![
+			plain("["),
+			tagged("casings", TileType::J),
+			plain("|L]"),
+		]);
+	}
+
+	#[test]
+	fn shipped_keywords_point_at_their_case_evidence() {
+		for name in ["level_0", "level_1", "level_2"] {
+			let path = format!("{}/assets/levels/{}.level.ron", env!("CARGO_MANIFEST_DIR"), name);
+			let level: crate::levels::LevelAsset = ron::de::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+			for case in level.cases.iter() {
+				let keywords: Vec<TileType> = parse_markup(&case.report).iter().filter_map(|span| span.keyword).collect();
+				assert!(!keywords.is_empty(), "{}: untagged report {:?}", name, case.report);
+				for keyword in keywords {
+					assert!(case.solution.contains(&keyword), "{}: {:?} isn't evidence for {:?}", name, keyword, case.report);
+				}
+			}
+		}
+	}
+}
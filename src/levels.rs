@@ -0,0 +1,390 @@
+// Levels module for loading the campaign and level definitions from asset files
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, AsyncReadExt, io::Reader}, utils::BoxedFuture};
+use serde::{Deserialize, Serialize};
+
+use crate::{board::Solution, derivables::*, tiles::spawn_tile};
+
+pub struct LevelsPlugin;
+
+impl Plugin for LevelsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_asset::<LevelAsset>()
+			.init_asset::<CampaignAsset>()
+			.init_asset_loader::<LevelLoader>()
+			.init_asset_loader::<CampaignLoader>()
+			.init_resource::<LevelRegistry>()
+			.init_resource::<BoardConfig>()
+			.add_systems(Startup, (
+				load_campaign,
+			))
+			.add_systems(Update, (
+				register_levels,
+			))
+		;
+	}
+}
+
+// Level names in play order, loaded from assets/levels/campaign.ron
+#[derive(Asset, TypePath, Deserialize, Serialize, Clone, Debug)]
+pub struct CampaignAsset {
+	pub levels: Vec<String>,
+}
+
+// Everything needed to build a level, loaded from assets/levels/*.level.ron
+#[derive(Asset, TypePath, Deserialize, Serialize, Clone, Debug)]
+pub struct LevelAsset {
+	pub cases: Vec<CaseDef>,
+	pub tiles: Vec<TileDef>,
+	// Levels that leave it out get the original board
+	#[serde(default, skip_serializing_if = "is_default_board")]
+	pub board: BoardConfig,
+	// Evidence can't be placed under a thread unless the thread ends on it
+	#[serde(default, skip_serializing_if = "is_false")]
+	pub threads_block_tiles: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CaseDef {
+	// Words tagged like "[bullet casings|J]" light up the matching evidence when hovered
+	pub report: String,
+	// Suspect and evidence that must be threaded to this case's victim
+	pub solution: Vec<TileType>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TileDef {
+	pub tile: TileType,
+	pub at: TilePlacement,
+	// Quarter turns anticlockwise
+	#[serde(default, skip_serializing_if = "is_zero")]
+	pub rotation: u8,
+	// Flipped left to right before rotating
+	#[serde(default, skip_serializing_if = "is_false")]
+	pub mirrored: bool,
+	// Stops the player rotating or flipping the tile
+	#[serde(default, skip_serializing_if = "is_false")]
+	pub locked: bool,
+	// Overrides the thread group worked out from the tile type
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub group: Option<usize>,
+	// Index into the tile layout, 99 for random tack location
+	#[serde(default)]
+	pub tack: usize,
+	// Junk tacks with consecutive links get strung together
+	#[serde(default = "no_links", skip_serializing_if = "has_no_links")]
+	pub links: i32,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum TilePlacement {
+	// Grid index on the evidence board
+	Board(usize, usize),
+	// Free position on the workbench
+	Bench(f32, f32),
+}
+
+fn no_links() -> i32 {
+	-1
+}
+
+fn has_no_links(links: &i32) -> bool {
+	*links == -1
+}
+
+fn is_zero(rotation: &u8) -> bool {
+	*rotation == 0
+}
+
+fn is_false(flag: &bool) -> bool {
+	!*flag
+}
+
+fn is_default_board(board: &BoardConfig) -> bool {
+	*board == BoardConfig::default()
+}
+
+impl LevelAsset {
+	// Turn away boards the grid maths can't place tiles on, and cases that don't fit the thread groups
+	pub fn validate(&self) -> Result<(), LevelLoaderError> {
+		if self.board.width == 0 || self.board.height == 0 {
+			return Err(LevelLoaderError::EmptyBoard(self.board.width, self.board.height));
+		}
+		if self.cases.is_empty() || self.cases.len() > MAX_CASES {
+			return Err(LevelLoaderError::CaseCount(self.cases.len()));
+		}
+		// Groups past the cases are only good for the reserved ones
+		let cases = self.cases.len();
+		for (i, tile_def) in self.tiles.iter().enumerate() {
+			let victim_past_cases = matches!(tile_def.tile, TileType::V(case) if case >= cases);
+			let group = tile_def.group();
+			if victim_past_cases || (group >= cases && group < NEUTRAL_GROUP) || group > PIN_GROUP {
+				return Err(LevelLoaderError::TilePastCases(i));
+			}
+		}
+		Ok(())
+	}
+
+	pub fn solution(&self) -> Solution {
+		Solution {
+			cases: self.cases.iter().map(|case| case.solution.clone()).collect(),
+		}
+	}
+}
+
+impl TileDef {
+	pub fn location(&self, board: &BoardConfig) -> Vec3 {
+		match self.at {
+			TilePlacement::Board(x, y) => board.index_to_grid([(x, y)].to_vec())[0],
+			TilePlacement::Bench(x, y) => Vec3::new(x, y, 300.0),
+		}
+	}
+
+	pub fn orientation(&self) -> Orientation {
+		Orientation {
+			rotation: self.rotation % 4,
+			mirrored: self.mirrored,
+			locked: self.locked,
+		}
+	}
+
+	pub fn transform(&self, board: &BoardConfig) -> Transform {
+		let mut transform = Transform::from_translation(self.location(board));
+		self.orientation().apply(&mut transform);
+		transform
+	}
+
+	// Centres of the cells this tile covers, in world space
+	pub fn cell_locations(&self, board: &BoardConfig) -> Vec<Vec2> {
+		tilette_locations(self.tile, &self.orientation(), &self.transform(board))
+	}
+
+	pub fn group(&self) -> usize {
+		self.group.unwrap_or(match self.tile {
+			TileType::V(i) => i,
+			TileType::W(_) => JUNK_GROUP,
+			TileType::P => PIN_GROUP,
+			_ => NEUTRAL_GROUP,
+		})
+	}
+}
+
+#[derive(Debug)]
+pub enum LevelLoaderError {
+	Io(std::io::Error),
+	Ron(ron::error::SpannedError),
+	// Width and height of a board with no cells
+	EmptyBoard(usize, usize),
+	// Number of cases, when there are none or more than MAX_CASES
+	CaseCount(usize),
+	// Index of a tile whose victim or group is for a case the level doesn't have
+	TilePastCases(usize),
+}
+
+impl std::fmt::Display for LevelLoaderError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			LevelLoaderError::Io(err) => write!(f, "Could not read file: {}", err),
+			LevelLoaderError::Ron(err) => write!(f, "Could not parse file: {}", err),
+			LevelLoaderError::EmptyBoard(width, height) => write!(f, "Board has no cells: {}x{}", width, height),
+			LevelLoaderError::CaseCount(cases) => write!(f, "Level needs 1 to {} cases, has {}", MAX_CASES, cases),
+			LevelLoaderError::TilePastCases(tile) => write!(f, "Tile {} belongs to a case the level doesn't have", tile),
+		}
+	}
+}
+
+impl std::error::Error for LevelLoaderError {}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+	type Asset = LevelAsset;
+	type Settings = ();
+	type Error = LevelLoaderError;
+
+	fn load<'a>(
+		&'a self,
+		reader: &'a mut Reader,
+		_settings: &'a (),
+		_load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<LevelAsset, LevelLoaderError>> {
+		Box::pin(async move {
+			let level: LevelAsset = read_ron(reader).await?;
+			level.validate()?;
+			Ok(level)
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["level.ron"]
+	}
+}
+
+#[derive(Default)]
+pub struct CampaignLoader;
+
+impl AssetLoader for CampaignLoader {
+	type Asset = CampaignAsset;
+	type Settings = ();
+	type Error = LevelLoaderError;
+
+	fn load<'a>(
+		&'a self,
+		reader: &'a mut Reader,
+		_settings: &'a (),
+		_load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<CampaignAsset, LevelLoaderError>> {
+		Box::pin(async move {
+			read_ron(reader).await
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["campaign.ron"]
+	}
+}
+
+pub async fn read_ron<T: serde::de::DeserializeOwned>(
+	reader: &mut Reader<'_>,
+) -> Result<T, LevelLoaderError> {
+	let mut bytes = Vec::new();
+	reader.read_to_end(&mut bytes).await.map_err(LevelLoaderError::Io)?;
+	ron::de::from_bytes::<T>(&bytes).map_err(LevelLoaderError::Ron)
+}
+
+pub fn spawn_level_tiles(
+	commands: &mut Commands,
+	asset_server: &Res<AssetServer>,
+	level_asset: &LevelAsset,
+) -> Vec<Entity> {
+	let mut tiles = Vec::new();
+	for tile_def in level_asset.tiles.iter() {
+		let tile = spawn_tile(commands, tile_def.location(&level_asset.board), tile_def.tile, asset_server,
+			tile_def.group(), matches!(tile_def.tile, TileType::V(_)), tile_def.links,
+			matches!(tile_def.tile, TileType::C(_)), tile_def.tack);
+		commands.entity(tile).insert((tile_def.transform(&level_asset.board), tile_def.orientation(), TileId(tiles.len())));
+		match tile_def.tile {
+			TileType::C(_) | TileType::V(_) | TileType::W(_) | TileType::P => {commands.entity(tile).insert(Immovable);},
+			_ => (),
+		}
+		tiles.push(tile);
+	}
+	tiles
+}
+
+pub fn level_path(
+	name: &str,
+) -> String {
+	format!("levels/{}.level.ron", name)
+}
+
+fn load_campaign(
+	mut registry: ResMut<LevelRegistry>,
+	asset_server: Res<AssetServer>,
+) {
+	registry.campaign = asset_server.load("levels/campaign.ron");
+}
+
+// Load every level the campaign lists, again whenever the campaign file changes
+fn register_levels(
+	mut registry: ResMut<LevelRegistry>,
+	mut ev_r_campaign: EventReader<AssetEvent<CampaignAsset>>,
+	campaign_assets: Res<Assets<CampaignAsset>>,
+	asset_server: Res<AssetServer>,
+) {
+	for ev in ev_r_campaign.read() {
+		let (AssetEvent::Added{id} | AssetEvent::Modified{id}) = ev else {continue};
+		if *id != registry.campaign.id() {continue;};
+		let Some(campaign) = campaign_assets.get(*id) else {continue};
+		registry.names = campaign.levels.clone();
+		registry.handles = campaign.levels.iter()
+			.map(|name| asset_server.load(level_path(name)))
+			.collect();
+		// Nothing would ever finish loading
+		if registry.is_empty() {
+			warn!("levels/campaign.ron doesn't list any levels");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// A victim for each case and nothing else
+	fn level_with_cases(
+		cases: usize,
+	) -> LevelAsset {
+		LevelAsset {
+			cases: (0..cases).map(|_| CaseDef {report: String::new(), solution: Vec::new()}).collect(),
+			tiles: (0..cases).map(|case| tile_def(TileType::V(case), None)).collect(),
+			board: BoardConfig::default(),
+			threads_block_tiles: false,
+		}
+	}
+
+	fn tile_def(
+		tile: TileType,
+		group: Option<usize>,
+	) -> TileDef {
+		TileDef {
+			tile: tile,
+			at: TilePlacement::Bench(0.0, 0.0),
+			rotation: 0,
+			mirrored: false,
+			locked: false,
+			group: group,
+			tack: 0,
+			links: -1,
+		}
+	}
+
+	#[test]
+	fn empty_boards_are_rejected() {
+		let mut level = level_with_cases(3);
+		assert!(level.validate().is_ok());
+		for (width, height) in [(0, 12), (29, 0), (0, 0)] {
+			level.board.width = width;
+			level.board.height = height;
+			assert!(matches!(level.validate(), Err(LevelLoaderError::EmptyBoard(w, h)) if w == width && h == height));
+			// Placing tiles on it mustn't panic either
+			level.board.index_to_grid(vec![(3, 3)]);
+		}
+	}
+
+	#[test]
+	fn shipped_levels_are_valid() {
+		for name in ["level_0", "level_1", "level_2"] {
+			let path = format!("{}/assets/levels/{}.level.ron", env!("CARGO_MANIFEST_DIR"), name);
+			let level: LevelAsset = ron::de::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+			assert!(level.validate().is_ok(), "{}: {:?}", name, level.validate());
+		}
+	}
+
+	#[test]
+	fn case_counts_past_the_thread_groups_are_rejected() {
+		for cases in 1..=MAX_CASES {
+			assert!(level_with_cases(cases).validate().is_ok(), "{} cases", cases);
+		}
+		for cases in [0, MAX_CASES + 1] {
+			assert!(matches!(level_with_cases(cases).validate(), Err(LevelLoaderError::CaseCount(n)) if n == cases));
+		}
+	}
+
+	#[test]
+	fn tiles_past_the_last_case_are_rejected() {
+		let mut level = level_with_cases(3);
+		// Reserved groups are fine whatever the case count
+		for group in [NEUTRAL_GROUP, JUNK_GROUP, PIN_GROUP] {
+			level.tiles.push(tile_def(TileType::O, Some(group)));
+		}
+		level.tiles.push(tile_def(TileType::P, None));
+		assert!(level.validate().is_ok());
+		for (tile, group) in [(TileType::V(3), None), (TileType::O, Some(3)), (TileType::O, Some(MAX_CASES - 1)), (TileType::O, Some(PIN_GROUP + 1))] {
+			let mut level = level_with_cases(3);
+			level.tiles.push(tile_def(tile, group));
+			assert!(matches!(level.validate(), Err(LevelLoaderError::TilePastCases(3))), "{:?} in group {:?}", tile, group);
+		}
+	}
+}
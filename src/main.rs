@@ -6,14 +6,29 @@ use bevy::{prelude::*, asset::AssetMetaCheck};
 use bevy_kira_audio::prelude::*;
 
 // MODULES
+mod actions;
+mod board;
 mod buttons;
+mod camera;
+mod controls;
 mod derivables;
+mod editor;
+mod geometry;
+mod history;
+mod keywords;
+mod levels;
 mod post_processing;
+mod ropes;
+mod saves;
 mod setup;
 mod threads;
 mod casefiles;
 mod tiles;
+mod tooltips;
 
+// Only include in tests
+#[cfg(test)]
+mod testing;
 
 // Only include in debug builds
 #[cfg(debug_assertions)]
@@ -42,12 +57,25 @@ fn main() {
 		.add_plugins((
 			default_plugins,
 			AudioPlugin,
+			camera::CameraPlugin,
+		))
+		// Split up, add_plugins only takes so many at once
+		.add_plugins((
+			actions::ActionsPlugin,
 			buttons::ButtonsPlugin,
+			controls::ControlsPlugin,
+			levels::LevelsPlugin,
 			post_processing::PostProcessingPlugin,
+			ropes::RopesPlugin,
+			saves::SavesPlugin,
 			setup::SetupPlugin,
 			threads::ThreadsPlugin,
 			casefiles::CasefilesPlugin,
+			editor::EditorPlugin,
+			history::HistoryPlugin,
+			keywords::KeywordsPlugin,
 			tiles::TilesPlugin,
+			tooltips::TooltipsPlugin,
 		))
 	;
 
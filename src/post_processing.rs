@@ -1,6 +1,8 @@
 // Import Bevy game engine essentials
 use bevy::{prelude::*, render::{extract_component::{ExtractComponentPlugin, UniformComponentPlugin, ComponentUniforms, ExtractComponent}, render_graph::{RenderGraphApp, ViewNodeRunner, NodeRunError, ViewNode, RenderGraphContext}, RenderApp, render_resource::{Operations, RenderPassColorAttachment, RenderPassDescriptor, BindGroupEntry, BindingResource, PipelineCache, BindGroupLayout, Sampler, CachedRenderPipelineId, BindGroupLayoutDescriptor, BindGroupLayoutEntry, ShaderStages, BindingType, TextureSampleType, TextureViewDimension, SamplerBindingType, SamplerDescriptor, RenderPipelineDescriptor, FragmentState, ColorTargetState, TextureFormat, ColorWrites, PrimitiveState, MultisampleState, ShaderType, BindGroupEntries}, view::ViewTarget, renderer::{RenderContext, RenderDevice}, texture::BevyDefault}, core_pipeline::{core_2d, fullscreen_vertex_shader::fullscreen_shader_vertex_state}, ecs::query::QueryItem};
 
+use crate::derivables::*;
+
 pub struct PostProcessingPlugin;
 
 impl Plugin for PostProcessingPlugin {
@@ -12,7 +14,7 @@ impl Plugin for PostProcessingPlugin {
 			))
 			.add_systems(Update, (
 				update_settings,
-			))
+			).run_if(not(in_state(GameState::Controls))))
 		;
 
 		let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
@@ -200,10 +202,10 @@ pub struct PostProcessSettings {
 
 fn update_settings(
 	mut settings_query: Query<&mut PostProcessSettings>,
-	keyboard: Res<Input<KeyCode>>,
+	actions: Res<Actions>,
 	//time: Res<Time>,
 ) {
-	if keyboard.just_pressed(KeyCode::P) {
+	if actions.just_pressed(Action::Filter) {
 		for mut setting in settings_query.iter_mut() {
 			setting.intensity = (setting.intensity + 1.0) % 8.0;
 			//setting.intensity = (time.elapsed_seconds() * 5.0).sin() * 0.005;
@@ -0,0 +1,114 @@
+// Ropes module for drawing threads as hanging string instead of straight sticks
+use bevy::prelude::*;
+
+use crate::{derivables::*, threads::{detect_thread_collision, rope_segment_bundle}};
+
+pub struct RopesPlugin;
+
+impl Plugin for RopesPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_systems(Update, (
+				simulate_ropes,
+				draw_ropes,
+			).chain().after(detect_thread_collision).run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+// Threads being drawn hang loose, placed ones get pulled nearly straight
+const LOOSE_SLACK: f32 = 1.15;
+const TAUT_SLACK: f32 = 1.01;
+// How quickly slack is taken up once a thread is connected
+const SNAP_RATE: f32 = 25.0;
+const GRAVITY: Vec2 = Vec2::new(0.0, -900.0);
+const DAMPING: f32 = 0.96;
+const ITERATIONS: usize = 12;
+
+fn simulate_ropes(
+	mut rope_query: Query<(&Thread, &mut Rope, Has<Loose>)>,
+	pointer: Res<Pointer>,
+	time: Res<Time>,
+) {
+	let cursor_pos = pointer.pos;
+	// Long frames would fling the rope about
+	let dt = time.delta_seconds().min(1.0/30.0);
+
+	for (thread, mut rope, loose) in rope_query.iter_mut() {
+		// Loose threads carry on from their last tack to the cursor
+		let mut anchors = thread.tacks_locs.clone();
+		if loose {
+			let Some(cursor_pos) = cursor_pos else {continue};
+			anchors.push(cursor_pos);
+		}
+		if anchors.len() < 2 {continue;};
+		// Routing pins added while drawing get a fresh run of rope
+		while rope.runs() < anchors.len() - 1 {
+			let next = anchors[rope.runs() + 1];
+			rope.extend(next);
+		}
+		let target_slack = if loose {LOOSE_SLACK} else {TAUT_SLACK};
+		rope.slack += (target_slack - rope.slack) * (SNAP_RATE * dt).min(1.0);
+
+		let rope = &mut *rope;
+		let run = ROPE_POINTS - 1;
+		let pinned = |i: usize| i % run == 0;
+		for i in 0..rope.points.len() {
+			if pinned(i) {
+				rope.points[i] = anchors[i / run];
+				rope.previous[i] = anchors[i / run];
+				continue;
+			}
+			let velocity = (rope.points[i] - rope.previous[i]) * DAMPING;
+			rope.previous[i] = rope.points[i];
+			rope.points[i] += velocity + GRAVITY * dt * dt;
+		}
+
+		// Pull neighbouring points back to their rest distance, points on tacks stay put
+		let rests: Vec<f32> = anchors.windows(2).map(|ends| ends[0].distance(ends[1]) * rope.slack / run as f32).collect();
+		for _ in 0..ITERATIONS {
+			for i in 0..rope.points.len() - 1 {
+				let delta = rope.points[i + 1] - rope.points[i];
+				let distance = delta.length();
+				if distance <= f32::EPSILON {continue;};
+				let correction = delta * (distance - rests[i / run]) / distance;
+				match (pinned(i), pinned(i + 1)) {
+					(true, true) => (),
+					(true, false) => rope.points[i + 1] -= correction,
+					(false, true) => rope.points[i] += correction,
+					(false, false) => {
+						rope.points[i] += correction / 2.0;
+						rope.points[i + 1] -= correction / 2.0;
+					}
+				}
+			}
+		}
+	}
+}
+
+fn draw_ropes(
+	mut commands: Commands,
+	rope_query: Query<(Entity, &Thread, &Rope, &Children)>,
+	mut segment_query: Query<(&RopeSegment, &mut Transform)>,
+) {
+	for (rope_entity, thread, rope, children) in rope_query.iter() {
+		// Ropes grow when a loose thread is routed around a pin
+		if children.len() < rope.points.len() - 1 {
+			commands.entity(rope_entity).with_children(|parent| {
+				for i in children.len()..rope.points.len() - 1 {
+					parent.spawn(rope_segment_bundle(thread.group, i));
+				}
+			});
+		}
+		for &child in children.iter() {
+			let Ok((segment, mut segment_pos)) = segment_query.get_mut(child) else {continue};
+			let (a, b) = (rope.points[segment.0], rope.points[segment.0 + 1]);
+			segment_pos.translation = ((a + b)/2.0).extend(0.0);
+			if a != b {
+				segment_pos.rotation = Quat::from_rotation_arc(Vec3::Y, (b - a).normalize().extend(0.0));
+			}
+			// A little longer than the gap so the joins don't show
+			segment_pos.scale.y = a.distance(b) + 1.0;
+		}
+	}
+}
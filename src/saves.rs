@@ -0,0 +1,210 @@
+// Saves module for keeping board progress between level changes and play sessions
+use bevy::{prelude::*, app::AppExit};
+use bevy_pkv::PkvStore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{buttons::load_level, derivables::*, history::*};
+
+pub struct SavesPlugin;
+
+impl Plugin for SavesPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			// Stored in the user's data directory natively and in localStorage on the web
+			.insert_resource(PkvStore::new("SoysCodingCafe", "NoLooseThreads"))
+			.init_resource::<LevelSession>()
+			.add_systems(Startup, (
+				load_progress,
+			))
+			.add_systems(Update, (
+				save_on_level_change,
+				resume_board,
+			).before(load_level).run_if(in_state(GameState::Game)))
+			.add_systems(Update, (
+				track_session,
+				record_solve,
+				save_progress,
+			).chain().run_if(in_state(GameState::Game)))
+			.add_systems(Last, (
+				save_on_exit,
+			).run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+// Bumped whenever the shape of anything saved changes
+const SAVE_VERSION: u32 = 1;
+
+// Every entry carries the version it was written with, so ones from another version are dropped
+#[derive(Serialize, Deserialize)]
+struct Saved<T> {
+	version: u32,
+	data: T,
+}
+
+// None for anything missing or written by another version
+pub fn load_saved<T: DeserializeOwned>(
+	pkv: &PkvStore,
+	key: &str,
+) -> Option<T> {
+	pkv.get::<Saved<T>>(key).ok()
+		.filter(|saved| saved.version == SAVE_VERSION)
+		.map(|saved| saved.data)
+}
+
+pub fn store_saved<T: Serialize>(
+	pkv: &mut PkvStore,
+	key: &str,
+	data: T,
+) {
+	if let Err(err) = pkv.set(key, &Saved {version: SAVE_VERSION, data: data}) {
+		warn!("Could not save {}: {}", key, err);
+	}
+}
+
+// Moves made and time spent on the current attempt at a level
+#[derive(Resource, Default)]
+struct LevelSession {
+	moves: u32,
+	started: f32,
+}
+
+fn board_key(
+	name: &str,
+) -> String {
+	format!("board_{}", name)
+}
+
+fn save_board(
+	pkv: &mut PkvStore,
+	registry: &LevelRegistry,
+	level: usize,
+	snapshot: &BoardSnapshot,
+) {
+	let Some(name) = registry.name(level) else {return};
+	store_saved(pkv, &board_key(name), Some(snapshot));
+}
+
+// Overwritten rather than removed, an empty save resumes nothing
+pub fn clear_board(
+	pkv: &mut PkvStore,
+	registry: &LevelRegistry,
+	level: usize,
+) {
+	let Some(name) = registry.name(level) else {return};
+	store_saved(pkv, &board_key(name), None::<BoardSnapshot>);
+}
+
+fn save_on_level_change(
+	mut pkv: ResMut<PkvStore>,
+	mut ev_r_level: EventReader<LevelSelectedEvent>,
+	registry: Res<LevelRegistry>,
+	level: Res<Level>,
+	tile_query: Query<(&TileId, &Transform, &Orientation, &Children)>,
+	tack_query: Query<(Entity, &Tack)>,
+	thread_query: Query<(&Thread, Has<Loose>)>,
+	thread_count: Res<ThreadCount>,
+) {
+	for ev in ev_r_level.read() {
+		// The board on screen is the editor's, and the old save won't fit the edited level
+		if ev.edited {
+			clear_board(&mut pkv, &registry, ev.level);
+			continue;
+		}
+		// Nothing to save before the first level has been spawned
+		if tile_query.is_empty() {continue;};
+		let snapshot = capture_snapshot(&tile_query, &tack_query, &thread_query, &thread_count);
+		save_board(&mut pkv, &registry, level.0, &snapshot);
+	}
+}
+
+fn save_on_exit(
+	mut pkv: ResMut<PkvStore>,
+	mut ev_r_exit: EventReader<AppExit>,
+	registry: Res<LevelRegistry>,
+	level: Res<Level>,
+	tile_query: Query<(&TileId, &Transform, &Orientation, &Children)>,
+	tack_query: Query<(Entity, &Tack)>,
+	thread_query: Query<(&Thread, Has<Loose>)>,
+	thread_count: Res<ThreadCount>,
+) {
+	if ev_r_exit.read().next().is_none() || tile_query.is_empty() {return;};
+	let snapshot = capture_snapshot(&tile_query, &tack_query, &thread_query, &thread_count);
+	save_board(&mut pkv, &registry, level.0, &snapshot);
+}
+
+// Runs a frame after the level loads so the freshly spawned tiles exist
+fn resume_board(
+	mut commands: Commands,
+	mut thread_count: ResMut<ThreadCount>,
+	mut ev_r_action: EventReader<BoardActionEvent>,
+	mut ev_w_action: EventWriter<BoardActionEvent>,
+	mut tile_query: Query<(&TileId, &mut Transform, &mut Orientation, &Children)>,
+	mut tack_query: Query<(Entity, &mut Tack), Without<TileId>>,
+	thread_query: Query<(Entity, &Thread, Has<Loose>)>,
+	held_query: Query<Entity, With<Held>>,
+	pkv: Res<PkvStore>,
+	registry: Res<LevelRegistry>,
+	level: Res<Level>,
+) {
+	let loaded = ev_r_action.read().any(|ev| ev.0 == BoardAction::Load);
+	if !loaded {return;};
+	let Some(name) = registry.name(level.0) else {return};
+	// Cleared boards are stored as None, boards saved by another version are dropped
+	let Some(snapshot) = load_saved::<Option<BoardSnapshot>>(&pkv, &board_key(name)).flatten() else {return};
+	apply_snapshot(&mut commands, &snapshot, &mut thread_count,
+		&mut tile_query, &mut tack_query, &thread_query, &held_query);
+	ev_w_action.send(BoardActionEvent(BoardAction::Resume));
+}
+
+fn load_progress(
+	mut commands: Commands,
+	pkv: Res<PkvStore>,
+) {
+	let progress = load_saved::<Progress>(&pkv, "progress").unwrap_or_default();
+	commands.insert_resource(progress);
+}
+
+fn save_progress(
+	mut pkv: ResMut<PkvStore>,
+	progress: Res<Progress>,
+) {
+	if !progress.is_changed() || progress.is_added() {return;};
+	store_saved(&mut pkv, "progress", &*progress);
+}
+
+fn track_session(
+	mut session: ResMut<LevelSession>,
+	mut ev_r_action: EventReader<BoardActionEvent>,
+	time: Res<Time>,
+) {
+	for ev in ev_r_action.read() {
+		match ev.0 {
+			BoardAction::Load | BoardAction::Restart => {
+				session.moves = 0;
+				session.started = time.elapsed_seconds();
+			},
+			BoardAction::MoveTile | BoardAction::DrawThread | BoardAction::Unravel => session.moves += 1,
+			BoardAction::Resume => (),
+		}
+	}
+}
+
+fn record_solve(
+	mut progress: ResMut<Progress>,
+	mut ev_r_solve: EventReader<SolveResultEvent>,
+	session: Res<LevelSession>,
+	registry: Res<LevelRegistry>,
+	level: Res<Level>,
+	time: Res<Time>,
+) {
+	for ev in ev_r_solve.read() {
+		if !ev.verdict.is_solved() {continue;};
+		let Some(name) = registry.name(level.0) else {continue};
+		let level_progress = progress.levels.entry(name.to_string()).or_default();
+		let elapsed = time.elapsed_seconds() - session.started;
+		level_progress.solved = true;
+		level_progress.best_moves = Some(level_progress.best_moves.map_or(session.moves, |best| best.min(session.moves)));
+		level_progress.best_time = Some(level_progress.best_time.map_or(elapsed, |best| best.min(elapsed)));
+	}
+}
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 // Setup module, used for initial game setup and initialising resources
 use bevy::prelude::*;
-use bevy::render::camera::ScalingMode;
+use bevy::{core_pipeline::clear_color::ClearColorConfig, render::camera::ScalingMode};
 use bevy_kira_audio::prelude::*;
 
 use crate::derivables::*;
@@ -16,9 +16,15 @@ impl Plugin for SetupPlugin {
 			.add_state::<GameState>()
 			.add_event::<WaitForJunkEvent>()
 			.add_event::<SolveCaseEvent>()
+			.add_event::<SolveResultEvent>()
 			.add_event::<LevelSelectedEvent>()
-			.insert_resource(ThreadCount(Vec3::ZERO))
+			.add_event::<BoardActionEvent>()
+			.add_event::<UndoEvent>()
+			.add_event::<RedoEvent>()
+			.insert_resource(ThreadCount(Vec::new()))
 			.insert_resource(ThreadColliding(false))
+			.insert_resource(ThreadsBlockTiles(false))
+			.init_resource::<BoardModel>()
 			.insert_resource(Level(0))
 			.insert_resource(SplashCount(0))
 			.insert_resource(VolumeToggle{bgm: true, sfx: true})
@@ -28,9 +34,13 @@ impl Plugin for SetupPlugin {
 			.add_systems(Update, (
 				progress_splash_screens,
 			).run_if(in_state(GameState::Boot)))
-			.add_systems(OnEnter(GameState::Game), (
+			// Not OnEnter, returning from the editor would build the scene a second time
+			.add_systems(OnTransition{from: GameState::Boot, to: GameState::Game}, (
 				setup_game,
 			))
+			.add_systems(Update, (
+				spawn_grid,
+			).run_if(in_state(GameState::Game)))
 		;
 	}
 }
@@ -40,8 +50,44 @@ fn setup(
 	asset_server: Res<AssetServer>,
 	audio: Res<Audio>,
 ) {
-	// Spawn camera
+	// Spawn cameras, a fixed one for the desk and buttons, one that zooms and pans over the board,
+	// and a fixed one drawing case reports and textboxes over both
+	commands.spawn((Camera2dBundle{
+		transform: Transform::from_translation(Vec3::new(0.0, 0.0, 1000.0)),
+		projection: OrthographicProjection {
+			scaling_mode: ScalingMode::Fixed{width: VIEW_SIZE.x, height: VIEW_SIZE.y},
+			..default()
+		},
+		..default()
+		},
+		ViewCamera,
+	));
+	commands.spawn((Camera2dBundle{
+		camera: Camera {
+			order: 1,
+			..default()
+		},
+		camera_2d: Camera2d {
+			clear_color: ClearColorConfig::None,
+		},
+		transform: Transform::from_translation(BOARD_VIEW_CENTRE.extend(1000.0)),
+		projection: OrthographicProjection {
+			scaling_mode: ScalingMode::Fixed{width: BOARD_VIEW_SIZE.x, height: BOARD_VIEW_SIZE.y},
+			..default()
+		},
+		..default()
+		},
+		BoardCamera,
+		BOARD_LAYER,
+	));
 	commands.spawn((Camera2dBundle{
+		camera: Camera {
+			order: 2,
+			..default()
+		},
+		camera_2d: Camera2d {
+			clear_color: ClearColorConfig::None,
+		},
 		transform: Transform::from_translation(Vec3::new(0.0, 0.0, 1000.0)),
 		projection: OrthographicProjection {
 			scaling_mode: ScalingMode::Fixed{width: VIEW_SIZE.x, height: VIEW_SIZE.y},
@@ -49,6 +95,7 @@ fn setup(
 		},
 		..default()
 		},
+		OVERLAY,
 		PostProcessSettings {
             intensity: 0.0,
             ..default()
@@ -186,9 +233,9 @@ fn progress_splash_screens(
 	asset_server: Res<AssetServer>,
 	audio: Res<Audio>,
 	splash_query: Query<(Entity, &Splash)>,
-	mouse: Res<Input<MouseButton>>,
+	actions: Res<Actions>,
 ) {
-	if mouse.just_pressed(MouseButton::Left) {
+	if actions.just_pressed(Action::Select) {
 		splash_count.0 += 1;
 	}
 	for (entity, splash) in splash_query.iter() {
@@ -230,40 +277,20 @@ fn setup_game(
 		},
 		..default()
 	});
-
-	// Spawn grid
+	// The board camera gets its own copy of the painted board to zoom, the background is drawn a pixel per unit
+	let frame = BoardView::frame();
 	commands.spawn((SpriteBundle{
-		transform: Transform::from_xyz(-180.0, 200.0, 100.0),
+		transform: Transform::from_translation(frame.center().extend(1.0)),
+		texture: asset_server.load("sprites/background.png"),
 		sprite: Sprite {
-			custom_size: Some(GRID_SIZE),
-			color: Color::rgba(0.0, 0.0, 0.0, 0.0),
+			custom_size: Some(frame.size()),
+			rect: Some(Rect::new(frame.min.x + VIEW_SIZE.x/2.0, VIEW_SIZE.y/2.0 - frame.max.y, frame.max.x + VIEW_SIZE.x/2.0, VIEW_SIZE.y/2.0 - frame.min.y)),
 			..default()
 		},
 		..default()
-		}, 
-		Grid,
-	)).with_children(|parent| {
-		for x in 0..GRID_CELLS.x as usize {
-			for y in 0..GRID_CELLS.y as usize {
-				parent.spawn((
-					SpriteBundle {
-						transform: Transform::from_xyz(
-							x as f32 * CELL_SIZE - GRID_SIZE.x / 2.0 + CELL_SIZE / 2.0, 
-							y as f32 * CELL_SIZE - GRID_SIZE.y / 2.0 + CELL_SIZE / 2.0,
-							800.0),
-						sprite: Sprite {
-							custom_size: Some(Vec2::splat(CELL_SIZE-1.0)),
-							color: Color::rgba(0.2, 0.2, 0.2, 0.0),
-							..default()
-						},
-						..default()
-					},
-					Highlight,
-				));
-			}
-		}
-	})
-	;
+		},
+		BOARD_LAYER,
+	));
 
 	let position = Vec2::new(-200.0, 50.0);
 	let size = Vec2::new(550.0, 350.0);
@@ -278,6 +305,7 @@ fn setup_game(
 		..default()
 		},
 		SolveText,
+		OVERLAY,
 	)).with_children(|parent| {
 		parent.spawn((Text2dBundle{
 			text_2d_bounds: bevy::text::Text2dBounds{ size: Vec2::new(
@@ -295,7 +323,7 @@ fn setup_game(
 					color: Color::rgb(0.7, 0.9, 0.7),
 				}),
 				TextSection::new(
-				format!("\nAfter presenting the connections between the evidence, the three suspects were found guilty! \
+				format!("\nAfter presenting the connections between the evidence, the suspects were found guilty! \
 					Now to move onto the next case. Use the navigation arrows at the top right to move on to the next level!"),
 				TextStyle {
 					font: asset_server.load("fonts/XTypewriter.ttf"),
@@ -305,11 +333,12 @@ fn setup_game(
 			]).with_alignment(TextAlignment::Left),
 			..default()
 			},
+			OVERLAY,
 		));
 	});
 
 	let position = Vec2::new(-200.0, 50.0);
-	let size = Vec2::new(550.0, 250.0);
+	let size = Vec2::new(550.0, 400.0);
 	commands.spawn((SpriteBundle{
 		transform: Transform::from_xyz(position.x, position.y, -10.0),
 		sprite: Sprite {
@@ -320,6 +349,7 @@ fn setup_game(
 		..default()
 		},
 		FailText,
+		OVERLAY,
 	)).with_children(|parent| {
 		parent.spawn((Text2dBundle{
 			text_2d_bounds: bevy::text::Text2dBounds{ size: Vec2::new(
@@ -347,6 +377,8 @@ fn setup_game(
 			]).with_alignment(TextAlignment::Left),
 			..default()
 			},
+			VerdictText,
+			OVERLAY,
 		));
 	});
 
@@ -367,14 +399,16 @@ fn setup_game(
 	let mut buttons = Vec::new();
 	let locs = [
 		Vec3::new(506.5, 375.0, 55.0), Vec3::new(610.0, 375.0, 50.0), Vec3::new(713.5, 375.0, 55.0),
-		Vec3::new(515.0, 265.0, 50.0), Vec3::new(705.0, 265.0, 50.0), Vec3::new(610.0, 155.0, 50.0),
-		Vec3::new(610.0, 45.0, 50.0), Vec3::new((515.0 + 705.0)/2.0, 265.0, 50.0)
+		Vec3::new(515.0, 265.0, 50.0), Vec3::new(705.0, 265.0, 50.0), Vec3::new(554.0, 155.0, 50.0),
+		Vec3::new(610.0, 45.0, 50.0), Vec3::new((515.0 + 705.0)/2.0, 265.0, 50.0),
+		Vec3::new(427.0, 183.0, 50.0), Vec3::new(427.0, 127.0, 50.0), Vec3::new(714.0, 155.0, 50.0),
 	];
 
 	let sizes = [
 		Vec2::new(105.0, 112.0), Vec2::new(102.0, 112.0), Vec2::new(105.0, 112.0),
-		Vec2::new(80.0, 80.0), Vec2::new(80.0, 80.0), Vec2::new(312.0, 104.0),
+		Vec2::new(80.0, 80.0), Vec2::new(80.0, 80.0), Vec2::new(200.0, 104.0),
 		Vec2::new(312.0, 104.0), Vec2::new(80.0, 80.0),
+		Vec2::new(48.0, 52.0), Vec2::new(48.0, 52.0), Vec2::new(104.0, 104.0),
 	];
 
 	let paths = [
@@ -382,9 +416,11 @@ fn setup_game(
 		"sprites/UI/next.png".to_string(), "sprites/UI/music.png".to_string(), 
 		"sprites/UI/sfx.png".to_string(), "sprites/UI/restart.png".to_string(), 
 		"sprites/UI/solve.png".to_string(), "sprites/UI/help.png".to_string(),
+		"sprites/UI/undo.png".to_string(), "sprites/UI/redo.png".to_string(),
+		"sprites/UI/free_play.png".to_string(),
 	];
 
-	for i in 0..8 {
+	for i in 0..locs.len() {
 		buttons.push(UIInfo {
 			loc: locs[i],
 			size: sizes[i],
@@ -414,43 +450,128 @@ fn setup_game(
 	commands.spawn((Text2dBundle{
 		transform: Transform::from_xyz(610.0, 375.0, 60.0),
 		text_anchor: bevy::sprite::Anchor::Center,
-		text: Text::from_section(
+		text: Text::from_sections([
+			TextSection::new(
 			"1".to_string(),
 			TextStyle {
 				font: asset_server.load("fonts/XTypewriterBold.ttf"),
 				font_size: 96.0,
 				color: Color::rgb(0.9, 0.9, 0.9),
-			}).with_alignment(TextAlignment::Center),
+			}),
+			// Solved badge
+			TextSection::new(
+			String::new(),
+			TextStyle {
+				font: asset_server.load("fonts/XTypewriterBold.ttf"),
+				font_size: 22.0,
+				color: Color::rgb(0.7, 0.9, 0.7),
+			}),
+		]).with_alignment(TextAlignment::Center),
 		..default()
 		},
 		LevelText,
 	));
 
+	// Free play toggle label
+	commands.spawn((Text2dBundle{
+		transform: Transform::from_xyz(714.0, 155.0, 60.0),
+		text_anchor: bevy::sprite::Anchor::Center,
+		text: Text::from_sections([
+			TextSection::new(
+			"FREE\nPLAY\n".to_string(),
+			TextStyle {
+				font: asset_server.load("fonts/XTypewriterBold.ttf"),
+				font_size: 24.0,
+				color: Color::rgb(0.9, 0.9, 0.9),
+			}),
+			TextSection::new(
+			"Off".to_string(),
+			TextStyle {
+				font: asset_server.load("fonts/XTypewriterBold.ttf"),
+				font_size: 24.0,
+				color: Color::rgb(0.9, 0.9, 0.9),
+			}),
+		]).with_alignment(TextAlignment::Center),
+		..default()
+		},
+		FreePlayText,
+	));
+
 	let positions = [
 		Vec2::new(-450.0, 160.0), Vec2::new(100.0, 300.0), 
 		Vec2::new(-350.0, -280.0), Vec2::new(540.0, -240.0),
 		Vec2::new(160.0, 20.0),
 	];
 	let sizes = [
-		Vec2::new(500.0, 280.0), Vec2::new(300.0, 140.0),
-		Vec2::new(400.0, 140.0), Vec2::new(300.0, 170.0),
+		Vec2::new(500.0, 480.0), Vec2::new(300.0, 140.0),
+		Vec2::new(400.0, 190.0), Vec2::new(300.0, 290.0),
 		Vec2::new(440.0, 200.0),
 	];
 	let hints = [
 		"This is the evidence board! Use right click, or right click and drag, to draw threads between tacks. Connect threads from the victims \
-		to the suspects through the correct evidence.\n\nYou can hold shift and right click to unravel threads from the end. Don't tangle the threads!".to_string(),
+		to the suspects through the correct evidence.\n\nYou can hold shift and right click to unravel threads from the end. Don't tangle the threads! Scroll over the board to zoom and middle drag to pan.\n\nNo mouse? The arrow keys or a gamepad move \
+		a cursor around the board, Space picks up, E threads, X unravels and Tab jumps to the buttons. F1 opens the controls to rebind them.\n\nOn a touch screen, tap tacks to thread, long press to drag tiles, tap with two fingers to unravel and pinch to zoom.".to_string(),
 		"Victims, suspects, and other notes from the legal entities sharing this board can't be moved.".to_string(),
-		"This is the workbench where you've dumped the evidence. Left click and hold to drag tiles to and from the evidence board above.".to_string(),
-		"These are the case files for the crimes. Use them to work out which evidence goes with which crime!".to_string(),
-		"Once you think you have everything nicely tied up then press the Solve! button on the right to check! You can also freely navigate between levels \
-		using the buttons at the top right.".to_string(),
+		"This is the workbench where you've dumped the evidence. Left click and hold to drag tiles to and from the evidence board above. \
+		While dragging, press R or scroll to rotate and Q to flip.".to_string(),
+		"These are the case files for the crimes. Use them to work out which evidence goes with which crime! \
+		Click one to pin it open, then drag it around or off to the side.".to_string(),
+		"Once you think you have everything nicely tied up then press the Solve! button on the right to check! Solving a level unlocks the next one, \
+		or press Free Play next to Restart to open every level.".to_string(),
 	];
 
 	for i in 0..hints.len() {
 		generate_hint_textbox(&mut commands, &asset_server, positions[i], sizes[i], hints[i].clone());
 	}
 	
-	ev_r_level.send(LevelSelectedEvent{level: 0});
+	ev_r_level.send(LevelSelectedEvent{level: 0, edited: false});
+}
+
+// Rebuilt whenever a level brings a different board
+fn spawn_grid(
+	mut commands: Commands,
+	grid_query: Query<(Entity, With<Grid>)>,
+	board: Res<BoardConfig>,
+) {
+	if !board.is_changed() {return;};
+	for (grid_entity, _) in grid_query.iter() {
+		commands.entity(grid_entity).despawn_recursive();
+	}
+	let size = board.size();
+	commands.spawn((SpriteBundle{
+		transform: Transform::from_translation(board.origin.extend(100.0)),
+		sprite: Sprite {
+			custom_size: Some(size),
+			// The background only has the original board painted on
+			color: if *board == BoardConfig::default() {Color::rgba(0.0, 0.0, 0.0, 0.0)} else {Color::rgb(0.55, 0.4, 0.25)},
+			..default()
+		},
+		..default()
+		}, 
+		Grid,
+		BOARD_LAYER,
+	)).with_children(|parent| {
+		for x in 0..board.width {
+			for y in 0..board.height {
+				parent.spawn((
+					SpriteBundle {
+						transform: Transform::from_xyz(
+							x as f32 * CELL_SIZE - size.x / 2.0 + CELL_SIZE / 2.0, 
+							y as f32 * CELL_SIZE - size.y / 2.0 + CELL_SIZE / 2.0,
+							800.0),
+						sprite: Sprite {
+							custom_size: Some(Vec2::splat(CELL_SIZE-1.0)),
+							color: Color::rgba(0.2, 0.2, 0.2, 0.0),
+							..default()
+						},
+						..default()
+					},
+					Highlight((x, board.height - 1 - y)),
+					BOARD_LAYER,
+				));
+			}
+		}
+	});
 }
 
 fn generate_hint_textbox(
@@ -459,10 +580,49 @@ fn generate_hint_textbox(
 	position: Vec2,
 	size: Vec2,
 	hint: String,
+) -> Entity {
+	let textbox = generate_textbox(commands, position.extend(-10.0), size, [hint_section(asset_server, hint)].to_vec());
+	commands.entity(textbox).insert(HintText).id()
+}
+
+// Typewritten title for a textbox
+pub fn title_section(
+	asset_server: &Res<AssetServer>,
+	title: String,
+) -> TextSection {
+	TextSection::new(
+		title,
+	TextStyle {
+		font: asset_server.load("fonts/XTypewriterBold.ttf"),
+		font_size: 32.0,
+		color: Color::rgb(0.9, 0.7, 0.7),
+	})
+}
+
+// Typewritten body text for a textbox
+pub fn hint_section(
+	asset_server: &Res<AssetServer>,
+	text: String,
+) -> TextSection {
+	TextSection::new(
+		text,
+	TextStyle {
+		font: asset_server.load("fonts/XTypewriter.ttf"),
+		font_size: 26.0,
+		color: Color::rgb(0.9, 0.9, 0.9),
+	})
+}
+
+// Dark box with text wrapped inside it, the text is the box's only child. Drawn over the board however it's zoomed
+pub fn generate_textbox(
+	commands: &mut Commands,
+	position: Vec3,
+	size: Vec2,
+	sections: Vec<TextSection>,
 ) -> Entity {
 	let margin = 10.0;
 	commands.spawn((SpriteBundle{
-		transform: Transform::from_xyz(position.x, position.y, -10.0),
+		transform: Transform::from_translation(position),
 		sprite: Sprite {
 			custom_size: Some(size),
 			color: Color::rgba(0.0, 0.0, 0.0, 0.99),
@@ -470,7 +630,7 @@ fn generate_hint_textbox(
 		},
 		..default()
 		},
-		HintText,
+		OVERLAY,
 	)).with_children(|parent| {
 		parent.spawn((Text2dBundle{
 			text_2d_bounds: bevy::text::Text2dBounds{ size: Vec2::new(
@@ -479,24 +639,10 @@ fn generate_hint_textbox(
 			)},
 			transform: Transform::from_xyz(-size.x/2.0 + margin, size.y/2.0, 5.0),
 			text_anchor: bevy::sprite::Anchor::TopLeft,
-			text: Text::from_sections([
-				// TextSection::new(
-				// format!("Hint Title\n\n"),
-				// TextStyle {
-				// 	font: asset_server.load("fonts/XTypewriterBold.ttf"),
-				// 	font_size: 48.0,
-				// 	color: Color::rgb(0.9, 0.7, 0.7),
-				// }),
-				TextSection::new(
-					hint,
-				TextStyle {
-					font: asset_server.load("fonts/XTypewriter.ttf"),
-					font_size: 26.0,
-					color: Color::rgb(0.9, 0.9, 0.9),
-				}),
-			]).with_alignment(TextAlignment::Left),
+			text: Text::from_sections(sections).with_alignment(TextAlignment::Left),
 			..default()
 			},
+			OVERLAY,
 		));
 	}).id()
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+// Testing module with the seeded random cases shared by the property tests
+use rand::{rngs::StdRng, SeedableRng};
+
+// Random cases each property test runs
+pub const CASES: usize = 5000;
+
+// The same seed always runs the same cases, so a failure can be run again
+pub fn check_cases(
+	seed: u64,
+	mut check: impl FnMut(&mut StdRng),
+) {
+	let mut rng = StdRng::seed_from_u64(seed);
+	for _ in 0..CASES {
+		check(&mut rng);
+	}
+}
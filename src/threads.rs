@@ -1,363 +1,359 @@
 // Threads module for handling thread placement and collisions
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{prelude::*, render::view::RenderLayers, utils::{HashMap, HashSet}};
 
-use crate::derivables::*;
+use crate::{casefiles::drag_case_files, derivables::*, geometry::*};
 
 pub struct ThreadsPlugin;
 
 impl Plugin for ThreadsPlugin {
 	fn build(&self, app: &mut App) {
 		app
+			.init_resource::<ThreadCells>()
+			.init_resource::<ThreadCollisions>()
+			.init_resource::<ThreadBroadphase>()
 			.add_systems(Update, (
 				update_thread_endpoints,
+				rasterize_threads,
 				draw_thread,
 				detect_thread_collision,
 				recolor_tacks,
-			).chain().run_if(in_state(GameState::Game)))
+			).chain().after(drag_case_files).run_if(in_state(GameState::Game)))
 		;
 	}
 }
 
+pub fn spawn_thread(
+	commands: &mut Commands,
+	group: usize,
+	index: f32,
+	tacks: Vec<Entity>,
+	tacks_locs: Vec<Vec2>,
+) -> Entity {
+	let rope = Rope::new(&tacks_locs);
+	let links = rope.points.len() - 1;
+	commands.spawn((
+		SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 500.0)),
+		Thread {
+			group: group,
+			index: index,
+			tacks: tacks,
+			tacks_locs: tacks_locs,
+		},
+		rope,
+		RemoveOnReset,
+	)).with_children(|parent| {
+		for i in 0..links {
+			parent.spawn(rope_segment_bundle(group, i));
+		}
+	}).id()
+}
+
+// Ropes are drawn piece by piece, each stretched to fit between two of its points every frame
+pub fn rope_segment_bundle(
+	group: usize,
+	index: usize,
+) -> (SpriteBundle, RopeSegment, RenderLayers) {
+	(
+		SpriteBundle {
+			sprite: Sprite {
+				custom_size: Some(Vec2::new(6.0, 1.0)),
+				color: get_tack_color(group),
+				..default()
+			},
+			..default()
+		},
+		RopeSegment(index),
+		BOARD_LAYER,
+	)
+}
+
 fn update_thread_endpoints(
 	mut thread_query: Query<(&mut Thread, Without<Loose>)>,
 	tack_query: Query<(&GlobalTransform, With<Tack>)>,
 ) {
 	for (mut thread, _) in thread_query.iter_mut() {
-		if let Ok((tack_pos, _)) = tack_query.get(thread.tacks[0]) {
-			thread.tacks_locs[0] = tack_pos.translation().xy();
-		}
-		if let Ok((tack_pos, _)) = tack_query.get(thread.tacks[1]) {
-			thread.tacks_locs[1] = tack_pos.translation().xy();
+		// Only touch threads that moved so collision checks can skip the rest
+		for i in 0..thread.tacks.len() {
+			if let Ok((tack_pos, _)) = tack_query.get(thread.tacks[i]) {
+				if thread.tacks_locs[i] != tack_pos.translation().xy() {
+					thread.tacks_locs[i] = tack_pos.translation().xy();
+				}
+			}
 		}
 	}
 }
 
-fn detect_thread_collision(
-	mut colliding: ResMut<ThreadColliding>,
-	mut highlight_query: Query<(&GlobalTransform, &mut Sprite, With<Highlight>)>,
-	thread_query: Query<(&Thread, Without<Highlight>)>,
-	window_query: Query<&Window, With<PrimaryWindow>>,
-) {
-	let mut collision_locs = Vec::new();
-	let mut iter = thread_query.iter_combinations();
-	while let Some([
-		(thread_a, _),
-		(thread_b, _),
-	]) = iter.fetch_next() {
-		if thread_a.group == thread_b.group {continue;};
-		let window = window_query.get_single().unwrap();
-		if let Some(cursor_pos) = window.cursor_position() {
-			let cursor_pos = cursor_to_screen(cursor_pos, window);
+// Width of the buckets placed threads get sorted into for collision checks
+const BUCKET_SIZE: f32 = 80.0;
 
-			let (a1, b1, c1) = if thread_a.tacks_locs.len() > 1 {
-				let a1 = thread_a.tacks_locs[1].y - thread_a.tacks_locs[0].y;
-				let b1 = thread_a.tacks_locs[0].x - thread_a.tacks_locs[1].x;
-				let c1 = a1 * thread_a.tacks_locs[0].x + b1 * thread_a.tacks_locs[0].y;
-				(a1, b1, c1)
-			} else {
-				let a1 = cursor_pos.y - thread_a.tacks_locs[0].y;
-				let b1 = thread_a.tacks_locs[0].x - cursor_pos.x;
-				let c1 = a1 * thread_a.tacks_locs[0].x + b1 * thread_a.tacks_locs[0].y;
-				(a1, b1, c1)
-			};
+// Uniform grid over placed threads so only threads sharing a bucket get tested against each other
+#[derive(Resource, Default)]
+pub struct ThreadBroadphase {
+	segments: Vec<(usize, Segment)>,
+	buckets: HashMap<(i32, i32), Vec<usize>>,
+	// Crossings between placed threads, only worked out again when one moves
+	crossings: Vec<Vec2>,
+}
 
-			let (a2, b2, c2) = if thread_b.tacks_locs.len() > 1 {
-				let a2 = thread_b.tacks_locs[1].y - thread_b.tacks_locs[0].y;
-				let b2 = thread_b.tacks_locs[0].x - thread_b.tacks_locs[1].x;
-				let c2 = a2 * thread_b.tacks_locs[0].x + b2 * thread_b.tacks_locs[0].y;
-				(a2, b2, c2)
-			} else {
-				let a2 = cursor_pos.y - thread_b.tacks_locs[0].y;
-				let b2 = thread_b.tacks_locs[0].x - cursor_pos.x;
-				let c2 = a2 * thread_b.tacks_locs[0].x + b2 * thread_b.tacks_locs[0].y;
-				(a2, b2, c2)
-			};
+impl ThreadBroadphase {
+	fn bucket_range(segment: &Segment) -> impl Iterator<Item = (i32, i32)> {
+		let min = (segment.start.min(segment.end) / BUCKET_SIZE).floor();
+		let max = (segment.start.max(segment.end) / BUCKET_SIZE).floor();
+		(min.x as i32..=max.x as i32).flat_map(move |x| (min.y as i32..=max.y as i32).map(move |y| (x, y)))
+	}
 
-			let delta = a1 * b2 - a2 * b1;
+	fn rebuild(&mut self, segments: Vec<(usize, Segment)>) {
+		self.buckets.clear();
+		for (index, (_, segment)) in segments.iter().enumerate() {
+			for bucket in Self::bucket_range(segment) {
+				self.buckets.entry(bucket).or_default().push(index);
+			}
+		}
+		self.segments = segments;
 
-			if delta == 0.0 {
-				break;
+		let mut tested = HashSet::new();
+		self.crossings.clear();
+		for indices in self.buckets.values() {
+			for (i, &a) in indices.iter().enumerate() {
+				for &b in indices[i + 1..].iter() {
+					if !tested.insert((a, b)) {continue;};
+					self.crossings.extend(crossing(self.segments[a], self.segments[b]));
+				}
 			}
+		}
+	}
 
-			let collision_loc = Vec2::new(
-				(b2 * c1 - b1 * c2) / delta,
-				(a1 * c2 - a2 * c1) / delta,
-			);
+	// Placed threads that could touch the segment
+	fn candidates(&self, segment: &Segment) -> HashSet<usize> {
+		Self::bucket_range(segment)
+			.filter_map(|bucket| self.buckets.get(&bucket))
+			.flatten()
+			.copied()
+			.collect()
+	}
+}
 
-			let line_a = if thread_a.tacks_locs.len() > 1 {
-				(thread_a.tacks_locs[0], thread_a.tacks_locs[1])
-			} else {
-				(thread_a.tacks_locs[0], cursor_pos)
-			};
-			let line_b = if thread_b.tacks_locs.len() > 1 {
-				(thread_b.tacks_locs[0], thread_b.tacks_locs[1])
-			} else {
-				(thread_b.tacks_locs[0], cursor_pos)
-			};
+// Where two threads from different cases cross, threads chained through a tack don't count
+fn crossing(
+	(group_a, line_a): (usize, Segment),
+	(group_b, line_b): (usize, Segment),
+) -> Vec<Vec2> {
+	if group_a == group_b {return Vec::new();};
+	match line_a.intersection(&line_b) {
+		Intersection::None => Vec::new(),
+		Intersection::Point(collision_loc) => {
+			if line_a.meets_at_endpoint(&line_b, collision_loc) {Vec::new()} else {[collision_loc].to_vec()}
+		}
+		Intersection::Overlap(overlap) => [overlap.start, overlap.midpoint(), overlap.end].to_vec(),
+	}
+}
 
-			let perp_a = (collision_loc - line_a.0).dot(line_a.1 - line_a.0);
-			let perp_b = (collision_loc - line_b.0).dot(line_b.1 - line_b.0);
-			let mag_a = (line_a.1 - line_a.0).length_squared();
-			let mag_b = (line_b.1 - line_b.0).length_squared();
+// Sort thread segments by the cells they might cross for levels where threads block tiles
+fn rasterize_threads(
+	mut thread_cells: ResMut<ThreadCells>,
+	mut removed_threads: RemovedComponents<Thread>,
+	thread_query: Query<&Thread, Without<Loose>>,
+	changed_query: Query<(), Changed<Thread>>,
+	tack_query: Query<&Parent, With<Tack>>,
+	threads_block_tiles: Res<ThreadsBlockTiles>,
+	board: Res<BoardConfig>,
+) {
+	let removed = removed_threads.read().count() > 0;
+	if !removed && changed_query.is_empty() && !threads_block_tiles.is_changed() && !board.is_changed() {return;};
+	thread_cells.cells.clear();
+	if !threads_block_tiles.0 {return;};
+	for thread in thread_query.iter() {
+		for (tacks, line) in thread.tacks.windows(2).zip(thread.segments()) {
+			let (Ok(tile_a), Ok(tile_b)) = (tack_query.get(tacks[0]), tack_query.get(tacks[1])) else {continue};
+			let ends = [tile_a.get(), tile_b.get()];
+			rasterize_segment(&mut thread_cells, &board, line, ends);
+		}
+	}
+}
 
-			if 0.0 <= perp_a && perp_a <= mag_a
-			&& 0.0 <= perp_b && perp_b <= mag_b {
-				collision_locs.push(collision_loc);
-			}
+fn rasterize_segment(
+	thread_cells: &mut ThreadCells,
+	board: &BoardConfig,
+	line: Segment,
+	ends: [Entity; 2],
+) {
+	// Only cells under the segment's bounding box can be crossed
+	for cell in board.cells_in(Rect::from_corners(line.start, line.end)) {
+		thread_cells.cells.entry(cell).or_default().push((line, ends));
+	}
+}
 
-		}
+pub fn detect_thread_collision(
+	mut colliding: ResMut<ThreadColliding>,
+	mut thread_collisions: ResMut<ThreadCollisions>,
+	mut broadphase: ResMut<ThreadBroadphase>,
+	mut removed_threads: RemovedComponents<Thread>,
+	thread_query: Query<(&Thread, Has<Loose>)>,
+	changed_query: Query<(), Changed<Thread>>,
+	pointer: Res<Pointer>,
+	board: Res<BoardConfig>,
+) {
+	let removed = removed_threads.read().count() > 0;
+	if removed || !changed_query.is_empty() {
+		broadphase.rebuild(thread_query.iter()
+			.filter(|(_, loose)| !loose)
+			.flat_map(|(thread, _)| thread.segments().map(|segment| (thread.group, segment)))
+			.collect());
 	}
+	let mut collision_locs = broadphase.crossings.clone();
 
-	if !collision_locs.is_empty() {
-		colliding.0 = true;
-		// println!("Total Collisions: {}", collision_locs.len());
-		// println!("Colliding!");
-		for (transform, mut highlight_sprite, _) in highlight_query.iter_mut() {
-			for collision in collision_locs.iter() {
-				//println!("Collision at: {}", collision);
-				let quant_loc = (*collision/40.0).floor() * 40.0 + 20.0;
-				//println!("Quant to: {}", quant_loc);
-				//println!("Highlight at: {}", transform.translation().xy());
-				if transform.translation().xy() == quant_loc {
-					highlight_sprite.color = Color::rgba(1.0, 0.0, 0.0, 0.7);
-					break;
-				} else {
-					highlight_sprite.color = Color::rgba(1.0, 1.0, 1.0, 0.0);
+	// Loose threads run from their last tack to the cursor, so they get checked every frame
+	if let Some(cursor_pos) = pointer.pos {
+		for (thread, loose) in thread_query.iter() {
+			if !loose {continue;};
+			let to_cursor = Segment::new(*thread.tacks_locs.last().unwrap(), cursor_pos);
+			for line in thread.segments().chain([to_cursor]) {
+				for index in broadphase.candidates(&line) {
+					collision_locs.extend(crossing((thread.group, line), broadphase.segments[index]));
 				}
 			}
 		}
+	}
 
-	} else {
-		colliding.0 = false;
-		for (_, mut highlight_sprite, _) in highlight_query.iter_mut() {
-			highlight_sprite.color = Color::rgba(1.0, 1.0, 1.0, 0.0);
-		}
+	colliding.0 = !collision_locs.is_empty();
+	let cells: HashSet<(usize, usize)> = collision_locs.into_iter().filter_map(|loc| board.cell_at(loc)).collect();
+	if cells != thread_collisions.cells {
+		thread_collisions.cells = cells;
 	}
 }
 
 fn draw_thread(
 	mut commands: Commands,
+	mut ev_w_action: EventWriter<BoardActionEvent>,
 	mut thread_count: ResMut<ThreadCount>,
 	mut tack_query: Query<(Entity, &GlobalTransform, &mut Tack)>,
-	mut thread_query: Query<(Entity, &mut Transform, &mut Thread, (Without<Loose>, Without<Tack>))>,
-	mut loose_thread_query: Query<(Entity, &mut Transform, &mut Thread, (With<Loose>, Without<Tack>))>,
-	grid_query: Query<(&Transform, (With<Grid>, Without<Tack>, Without<Thread>, Without<Loose>))>,
-	window_query: Query<&Window, With<PrimaryWindow>>,
-	mouse: Res<Input<MouseButton>>,
-	keyboard: Res<Input<KeyCode>>,
+	thread_query: Query<(Entity, &Thread, (Without<Loose>, Without<Tack>))>,
+	mut loose_thread_query: Query<(Entity, &mut Thread, (With<Loose>, Without<Tack>))>,
+	board: Res<BoardConfig>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
 	colliding: Res<ThreadColliding>,
+	case_report_stack: Res<CaseReportStack>,
 ) {
-	let shift = keyboard.pressed(KeyCode::ShiftLeft) || keyboard.pressed(KeyCode::ShiftRight);
-	let window = window_query.get_single().unwrap();
-	if let Some(cursor_pos) = window.cursor_position() {
-		let cursor_pos = cursor_to_screen(cursor_pos, window);
-		// if mouse.just_pressed(MouseButton::Right) {
-		// 	for (tack_entity, tack_pos, mut tack) in tack_query.iter_mut() {
-		// 		if (cursor_pos.x - tack_pos.translation().x).abs() < CELL_SIZE/2.0
-		// 		&& (cursor_pos.y - tack_pos.translation().y).abs() < CELL_SIZE/2.0 {
-		// 			if loose_thread_query.is_empty() {
-		// 				println!("Thread created!");
-		// 				let mut thread = Vec::new();
-		// 				thread.push(tack_entity);
-		// 				let thread_entity = commands.spawn((
-		// 					SpriteBundle {
-		// 						transform: Transform::from_xyz(
-		// 							tack_pos.translation().x,
-		// 							tack_pos.translation().y,
-		// 							500.0,
-		// 						),
-		// 						sprite: Sprite {
-		// 							custom_size: Some(Vec2::new(8.0, 1.0)),
-		// 							color: Color::rgba(0.8, 0.0, 0.0, 1.0),
-		// 							..default()
-		// 						},
-		// 						..default()
-		// 					},
-		// 					Thread(thread),
-		// 					Loose,
-		// 				)).id();
-		// 				break;
-		// 			} else {
-		// 				for (thread_entity, _, mut thread, _) in loose_thread_query.iter_mut() {
-		// 					thread.0.push(tack_entity);
-		// 					tack.0.push(thread_entity);
-		// 					commands.entity(thread_entity).remove::<Loose>();
-		// 				}
-		// 				break;
-		// 			}
-		// 		}
-		// 	}
-		// } else if mouse.just_pressed(MouseButton::Middle) {
-		// 	for (_, tack_pos, mut tack) in tack_query.iter_mut() {
-		// 		if (cursor_pos.x - tack_pos.translation().x).abs() < CELL_SIZE/2.0
-		// 		&& (cursor_pos.y - tack_pos.translation().y).abs() < CELL_SIZE/2.0 {
-		// 			while let Some(thread_entity) = tack.0.pop() {
-		// 				commands.entity(thread_entity).despawn_recursive();
-		// 			}
-		// 		}
-		// 	}
-		// }
-		for (grid_pos, _) in grid_query.iter() {
-			let on_grid = (cursor_pos.x - grid_pos.translation.x).abs() < GRID_SIZE.x/2.0
-				&& (cursor_pos.y - grid_pos.translation.y).abs() < GRID_SIZE.y/2.0;
-			if mouse.just_pressed(MouseButton::Right) || mouse.just_released(MouseButton::Right) {
-				let mut tack_clicked = false;
-				if on_grid {
-					for (tack_entity, tack_pos, mut tack) in tack_query.iter_mut() {
-						if (cursor_pos.x - tack_pos.translation().x).abs() < CELL_SIZE/2.0
-						&& (cursor_pos.y - tack_pos.translation().y).abs() < CELL_SIZE/2.0 {
-							// Click on valid tack to create thread
-							if loose_thread_query.is_empty() && tack.end && tack.group < 3 && !tack.suspect && mouse.just_pressed(MouseButton::Right) && !shift {
-								thread_count.0[tack.group] += 1.0;
-								// println!("Thread created! There are now {} threads in group {}", thread_count.0[tack.group], tack.group);
-								let mut tacks = Vec::new();
-								tacks.push(tack_entity);
-								let mut tacks_locs = Vec::new();
-								tacks_locs.push(tack_pos.translation().xy());
-								let thread_entity = commands.spawn((
-									SpriteBundle {transform: Transform::from_xyz(
-											tack_pos.translation().x,
-											tack_pos.translation().y,
-											500.0,),
-										sprite: Sprite {custom_size: Some(Vec2::new(6.0, 1.0)),
-											color: get_tack_color(tack.group),
-											..default()},
-										..default()},
-									Thread {group: tack.group,
-										index: thread_count.0[tack.group],
-										tacks: tacks,
-										tacks_locs: tacks_locs},
-									Loose,
-									RemoveOnReset
-								)).id();
-								tack.end = false;
-								tack.used = true;
-								tack_clicked = true;
-								break;
-								
-								// Click on valid tack to place thread
-							} else if !loose_thread_query.is_empty() && !tack.used && !colliding.0 && tack.group == 3 && !shift {
-								// println!("Loose threads");
-								for (thread_entity, _, mut thread, _) in loose_thread_query.iter_mut() {
-									if tack_entity != thread.tacks[0] {
-										tack.group = thread.group;
-										thread.tacks.push(tack_entity);
-										thread.tacks_locs.push(tack_pos.translation().xy());
-										commands.entity(thread_entity).remove::<Loose>();
-										tack.end = true;
-										tack.used = true;
-										tack_clicked = true;
-										break;
-									} else {
-										tack_clicked = true;
-									}
+	let pressed = actions.just_pressed(Action::Thread);
+	// Releasing the mouse over another tack drags a thread to it, the board cursor only uses presses
+	let released = actions.just_released(Action::Thread) && !pointer.snapped;
+	let cancel = actions.just_pressed(Action::Cancel);
+	if let Some(cursor_pos) = pointer.pos {
+		// Pinned case reports cover the board underneath them
+		let on_grid = board.contains(cursor_pos) && !case_report_stack.hovered;
+		if pressed || released || cancel {
+			let mut tack_clicked = false;
+			if on_grid && !cancel {
+				for (tack_entity, tack_pos, mut tack) in tack_query.iter_mut() {
+					if (cursor_pos.x - tack_pos.translation().x).abs() < CELL_SIZE/2.0
+					&& (cursor_pos.y - tack_pos.translation().y).abs() < CELL_SIZE/2.0 {
+						// Click on valid tack to create thread
+						if loose_thread_query.is_empty() && tack.end && tack.group < thread_count.0.len() && !tack.suspect && pressed {
+							thread_count.0[tack.group] += 1.0;
+							let thread_entity = spawn_thread(&mut commands, tack.group, thread_count.0[tack.group],
+								[tack_entity].to_vec(), [tack_pos.translation().xy()].to_vec());
+							commands.entity(thread_entity).insert(Loose);
+							tack.end = false;
+							tack.used = true;
+							tack_clicked = true;
+							break;
+							
+							// Click on a routing pin to bend the thread around it
+						} else if !loose_thread_query.is_empty() && tack.group == PIN_GROUP && !colliding.0 {
+							for (_, mut thread, _) in loose_thread_query.iter_mut() {
+								if thread.tacks.last() != Some(&tack_entity) {
+									thread.tacks.push(tack_entity);
+									thread.tacks_locs.push(tack_pos.translation().xy());
 								}
+							}
+							tack_clicked = true;
+							break;
 
-								// If not final tack, generate additional thread for chaining
-								if mouse.just_pressed(MouseButton::Right) && !tack.suspect {
-									thread_count.0[tack.group] += 1.0;
-									// println!("Thread created! There are now {} threads in group {}", thread_count.0[tack.group], tack.group);
-									let mut tacks = Vec::new();
-									tacks.push(tack_entity);
-									let mut tacks_locs = Vec::new();
-									tacks_locs.push(tack_pos.translation().xy());
-									let thread_entity = commands.spawn((
-										SpriteBundle {transform: Transform::from_xyz(
-												tack_pos.translation().x,
-												tack_pos.translation().y,
-												500.0,),
-											sprite: Sprite {custom_size: Some(Vec2::new(6.0, 1.0)),
-												color: get_tack_color(tack.group),
-												..default()},
-											..default()},
-										Thread {group: tack.group,
-											index: thread_count.0[tack.group],
-											tacks: tacks,
-											tacks_locs: tacks_locs},
-										Loose,
-										RemoveOnReset
-									)).id();
-									tack.end = false;
+							// Click on valid tack to place thread
+						} else if !loose_thread_query.is_empty() && !tack.used && !colliding.0 && tack.group == NEUTRAL_GROUP {
+							for (thread_entity, mut thread, _) in loose_thread_query.iter_mut() {
+								if tack_entity != thread.tacks[0] {
+									tack.group = thread.group;
+									thread.tacks.push(tack_entity);
+									thread.tacks_locs.push(tack_pos.translation().xy());
+									commands.entity(thread_entity).remove::<Loose>();
+									ev_w_action.send(BoardActionEvent(BoardAction::DrawThread));
+									tack.end = true;
 									tack.used = true;
 									tack_clicked = true;
 									break;
+								} else {
+									tack_clicked = true;
 								}
+							}
 
-								if tack_clicked {
-									break;
-								}
-								// Click on an invalid tack
-							} else if !loose_thread_query.is_empty() && tack.used && !shift {
-								for (_, _, thread, _) in loose_thread_query.iter_mut() {
-									if tack_entity == thread.tacks[0] {
-										tack_clicked = true;
-									}
-								}
-								// Keep presses valid for dragging threads
-							} else if mouse.just_pressed(MouseButton::Right) && !shift {
+							// If not final tack, generate additional thread for chaining
+							if pressed && !tack.suspect {
+								thread_count.0[tack.group] += 1.0;
+								let thread_entity = spawn_thread(&mut commands, tack.group, thread_count.0[tack.group],
+									[tack_entity].to_vec(), [tack_pos.translation().xy()].to_vec());
+								commands.entity(thread_entity).insert(Loose);
+								tack.end = false;
+								tack.used = true;
 								tack_clicked = true;
+								break;
 							}
+
+							if tack_clicked {
+								break;
+							}
+							// Click on an invalid tack
+						} else if !loose_thread_query.is_empty() && tack.used {
+							for (_, thread, _) in loose_thread_query.iter() {
+								if tack_entity == thread.tacks[0] {
+									tack_clicked = true;
+								}
+							}
+							// Keep presses valid for dragging threads
+						} else if pressed {
+							tack_clicked = true;
 						}
 					}
 				}
-				// If no tacks clicked then delete thread
-				if !tack_clicked {
-					for (thread_entity, _, thread, _) in loose_thread_query.iter_mut() {
-						if let Ok((_, _, mut tack)) = tack_query.get_mut(thread.tacks[0]) {
-							tack.end = true;
-							tack.used = false;
-						}
-						commands.entity(thread_entity).despawn_recursive();
-						thread_count.0[thread.group] -= 1.0;
+			}
+			// If no tacks clicked then delete thread
+			if !tack_clicked {
+				for (thread_entity, thread, _) in loose_thread_query.iter() {
+					if let Ok((_, _, mut tack)) = tack_query.get_mut(thread.tacks[0]) {
+						tack.end = true;
+						tack.used = false;
 					}
+					commands.entity(thread_entity).despawn_recursive();
+					thread_count.0[thread.group] -= 1.0;
 				}
 			}
 		}
 
-		if shift && mouse.just_pressed(MouseButton::Right) {
-			for (thread_entity, _, thread, _) in thread_query.iter_mut() {
+		if actions.just_pressed(Action::Unravel) && !case_report_stack.hovered {
+			for (thread_entity, thread, _) in thread_query.iter() {
 				if thread.index == -1.0 {continue;};
-				for endpoint in &thread.tacks_locs {
+				// Pins in the middle of a thread can't be grabbed to unravel it
+				for endpoint in [thread.tacks_locs[0], *thread.tacks_locs.last().unwrap()] {
 					if (cursor_pos.x - endpoint.x).abs() < CELL_SIZE/2.0
 					&& (cursor_pos.y - endpoint.y).abs() < CELL_SIZE/2.0 {
 						if thread.index == thread_count.0[thread.group] {
-							if let Ok((_, _, mut tack)) = tack_query.get_mut(thread.tacks[1]) {
+							if let Ok((_, _, mut tack)) = tack_query.get_mut(*thread.tacks.last().unwrap()) {
 								tack.end = false;
 								tack.used = false;
-								tack.group = 3;
+								tack.group = NEUTRAL_GROUP;
 							}
 							if let Ok((_, _, mut tack)) = tack_query.get_mut(thread.tacks[0]) {
 								tack.end = true;
 							}
 							commands.entity(thread_entity).despawn_recursive();
 							thread_count.0[thread.group] -= 1.0;
+							ev_w_action.send(BoardActionEvent(BoardAction::Unravel));
 						}
 					}
 				}
 			}
 		}
-
-		for (_, mut thread_pos, thread, _) in loose_thread_query.iter_mut() {
-			if let Ok((_, tack_pos_a, _)) = tack_query.get(thread.tacks[0]) {
-				thread_pos.translation.x = (tack_pos_a.translation().x + cursor_pos.x)/2.0;
-				thread_pos.translation.y = (tack_pos_a.translation().y + cursor_pos.y)/2.0;
-				let direction = (cursor_pos - tack_pos_a.translation().xy()).normalize();
-				thread_pos.rotation = Quat::from_rotation_arc(Vec3::Y, direction.extend(0.0));
-				thread_pos.scale.y = cursor_pos.distance(tack_pos_a.translation().xy()); // Divide by sprite height
-			}
-		}
-
-		for (_, mut thread_pos, thread, _) in thread_query.iter_mut() {
-			if let Ok((_, tack_pos_a, _)) = tack_query.get(thread.tacks[0]) {
-				if let Ok((_, tack_pos_b, _)) = tack_query.get(thread.tacks[1]) {
-					thread_pos.translation.x = (tack_pos_a.translation().x + tack_pos_b.translation().x)/2.0;
-					thread_pos.translation.y = (tack_pos_a.translation().y + tack_pos_b.translation().y)/2.0;
-					let direction = (tack_pos_b.translation().xy() - tack_pos_a.translation().xy()).normalize();
-					thread_pos.rotation = Quat::from_rotation_arc(Vec3::Y, direction.extend(0.0));
-					thread_pos.scale.y = tack_pos_b.translation().xy().distance(tack_pos_a.translation().xy()); // Divide by sprite height
-				}
-			}
-		}
 	}
 }
 
@@ -367,4 +363,92 @@ fn recolor_tacks(
 	for (mut sprite, tack) in tack_query.iter_mut() {
 		sprite.color = get_tack_color(tack.group);
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::{rngs::StdRng, Rng, SeedableRng};
+	use std::time::{Duration, Instant};
+
+	const THREADS: usize = 250;
+	const FRAMES: usize = 300;
+
+	// Headless app running only the collision check over a board strung with random short threads
+	fn collision_app() -> (App, Vec<Entity>) {
+		let mut app = App::new();
+		app
+			.insert_resource(ThreadColliding(false))
+			.init_resource::<ThreadCollisions>()
+			.init_resource::<ThreadBroadphase>()
+			.init_resource::<Pointer>()
+			.init_resource::<BoardConfig>()
+			.add_systems(Update, detect_thread_collision);
+
+		let board = BoardConfig::default();
+		let corner = board.origin - board.size()/2.0;
+		let mut rng = StdRng::seed_from_u64(15);
+		let mut threads = Vec::new();
+		for i in 0..THREADS {
+			let mut random_loc = || corner + Vec2::new(rng.gen_range(0.0..board.size().x), rng.gen_range(0.0..board.size().y));
+			// Short threads like real ones
+			let start = random_loc();
+			let end = start + (random_loc() - start).clamp_length_max(CELL_SIZE * 6.0);
+			let tacks = [app.world.spawn_empty().id(), app.world.spawn_empty().id()].to_vec();
+			threads.push(app.world.spawn(Thread {
+				group: i % MAX_CASES,
+				index: 0.0,
+				tacks: tacks,
+				tacks_locs: [start, end].to_vec(),
+			}).id());
+		}
+		// One thread still being strung to the cursor
+		let tack = app.world.spawn_empty().id();
+		app.world.spawn((Thread {
+			group: 0,
+			index: 0.0,
+			tacks: [tack].to_vec(),
+			tacks_locs: [board.origin].to_vec(),
+		}, Loose));
+		(app, threads)
+	}
+
+	#[test]
+	fn crossings_match_every_pair() {
+		let (mut app, _) = collision_app();
+		app.update();
+		let segments: Vec<(usize, Segment)> = app.world.query_filtered::<&Thread, Without<Loose>>()
+			.iter(&app.world)
+			.flat_map(|thread| thread.segments().map(|segment| (thread.group, segment)))
+			.collect();
+		let mut expected = 0;
+		for (i, a) in segments.iter().enumerate() {
+			for b in segments[i + 1..].iter() {
+				expected += crossing(*a, *b).len();
+			}
+		}
+		assert_eq!(app.world.resource::<ThreadBroadphase>().crossings.len(), expected);
+		assert!(app.world.resource::<ThreadColliding>().0);
+	}
+
+	// Frame time with 250 threads, one dragged and the cursor moving every frame
+	// cargo test --release bench_thread_collisions -- --ignored --nocapture
+	#[test]
+	#[ignore]
+	fn bench_thread_collisions() {
+		let (mut app, threads) = collision_app();
+		app.update();
+		let mut rng = StdRng::seed_from_u64(16);
+		let mut total = Duration::ZERO;
+		for frame in 0..FRAMES {
+			let nudge = Vec2::new(rng.gen_range(-CELL_SIZE..CELL_SIZE), rng.gen_range(-CELL_SIZE..CELL_SIZE));
+			app.world.get_mut::<Thread>(threads[frame % THREADS]).unwrap().tacks_locs[1] += nudge;
+			app.world.resource_mut::<Pointer>().pos = Some(nudge * 10.0);
+			let started = Instant::now();
+			app.update();
+			total += started.elapsed();
+		}
+		println!("{} threads: {:.3} ms average collision check over {} frames, {} crossings",
+			THREADS, total.as_secs_f64() * 1000.0 / FRAMES as f64, FRAMES,
+			app.world.resource::<ThreadBroadphase>().crossings.len());
+	}
+}
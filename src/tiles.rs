@@ -1,17 +1,29 @@
 // Tiles module for handling tile placement and collisions
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{prelude::*, render::view::RenderLayers, transform::TransformSystem};
 use bevy_kira_audio::{Audio, AudioControl};
 
-use crate::derivables::*;
+use crate::{casefiles::drag_case_files, derivables::*, threads::detect_thread_collision};
 
 pub struct TilesPlugin;
 
 impl Plugin for TilesPlugin {
 	fn build(&self, app: &mut App) {
 		app
+			.insert_resource(BoardOccupancy::new(0, 0))
+			.init_resource::<PlacementPreview>()
+			.init_resource::<HoveredTile>()
 			.add_systems(Update, (
-				drag_and_drop,
+				drag_and_drop.after(drag_case_files),
+				track_hovered_tile.after(drag_and_drop),
+				preview_placement.after(drag_and_drop).after(detect_thread_collision),
+				update_highlights.after(preview_placement),
 			).run_if(in_state(GameState::Game)))
+			// Catch up once spawns, drops and despawns have been applied
+			.add_systems(PostUpdate, (
+				update_occupancy,
+				orient_tiles.before(TransformSystem::TransformPropagate),
+				sort_tile_layers,
+			))
 		;
 	}
 }
@@ -37,6 +49,8 @@ pub fn spawn_tile(
 		..default()
 	},
 	Tile(tile_type),
+	Orientation::default(),
+	RenderLayers::default(),
 	RemoveOnReset,
 	)).with_children(|parent| {
 		let total_tilettes = tile_type.get_layout().len();
@@ -54,13 +68,14 @@ pub fn spawn_tile(
 					},
 					..default()
 				},
-				Tilette,
+				Tilette(loc),
+				RenderLayers::default(),
 			));
 			if tile_count == tack_tilette {
 				parent.spawn((
 					SpriteBundle{
 						transform: Transform::from_xyz(TILE_OFFSETS[loc.0][loc.1].x, TILE_OFFSETS[loc.0][loc.1].y, 300.0),
-						texture: asset_server.load(if tack_group == 4 {"sprites/pin.png"} else {"sprites/pin_alt.png"}),
+						texture: asset_server.load(if tack_group == JUNK_GROUP || tack_group == PIN_GROUP {"sprites/pin.png"} else {"sprites/pin_alt.png"}),
 						sprite: Sprite {
 							custom_size: Some(Vec2::new(CELL_SIZE - 20.0, CELL_SIZE - 20.0)),
 							color: get_tack_color(tack_group),
@@ -69,6 +84,7 @@ pub fn spawn_tile(
 						..default()
 					},
 					Tack {
+						cell: loc,
 						group: tack_group,
 						end: origin,
 						used: false,
@@ -76,6 +92,7 @@ pub fn spawn_tile(
 						suspect: suspect,
 						tile_type: tile_type,
 					},
+					RenderLayers::default(),
 				));
 			}
 			tile_count += 1;
@@ -83,76 +100,128 @@ pub fn spawn_tile(
 	}).id()
 }
 
+// Where a held tile would end up if it were dropped now
+struct Placement {
+	// Snapped onto the board, or left as is off it
+	transform: Transform,
+	// Board cells the tile would cover and whether each is free
+	cells: Vec<((usize, usize), bool)>,
+	on_board: bool,
+	valid: bool,
+}
+
+fn check_placement(
+	tile_entity: Entity,
+	tile_type: TileType,
+	orientation: &Orientation,
+	tile_pos: &Transform,
+	cursor_pos: Vec2,
+	board: &BoardConfig,
+	occupancy: &BoardOccupancy,
+	thread_cells: &ThreadCells,
+	thread_colliding: bool,
+) -> Placement {
+	let mut placement = Placement {
+		transform: *tile_pos,
+		cells: Vec::new(),
+		on_board: false,
+		valid: !thread_colliding,
+	};
+	if board.contains(cursor_pos) {
+		placement.on_board = true;
+		placement.transform.translation = board.snap_tile(tile_pos.translation.xy()).extend(tile_pos.translation.z);
+		// Every cell has to be on the board and not already covered by a tile or thread
+		for loc in tilette_locations(tile_type, orientation, &placement.transform) {
+			match board.cell_at(loc) {
+				Some(cell) => {
+					// A thread only lets a tile sit under it if it ends on that tile.
+					// Shrunk a little so threads running corner to corner don't block the cells beside them
+					let cell_rect = board.cell_rect(cell).inset(-1.0);
+					let under_thread = thread_cells.near(cell)
+						.any(|(line, ends)| !ends.contains(&tile_entity) && line.intersects_rect(cell_rect));
+					let free = occupancy.is_free(cell) && !under_thread;
+					placement.valid &= free;
+					placement.cells.push((cell, free));
+				}
+				None => placement.valid = false,
+			}
+		}
+	} else if tile_pos.translation.y > -50.0 - 80.0 {
+		placement.valid = false;
+	}
+	placement
+}
+
 fn drag_and_drop(
 	mut commands: Commands,
-	mut held_query: Query<(Entity, &Children, &mut Transform, &Held)>,
-	tile_query: Query<(Entity, &Transform, (With<Tile>, Without<Held>, Without<Immovable>))>,
-	grid_query: Query<(&Transform, (With<Grid>, Without<Tile>, Without<Held>))>,
-	tilette_query: Query<(&Parent, &GlobalTransform, (With<Tilette>, Without<Grid>, Without<Tile>, Without<Held>))>,
-	window_query: Query<&Window, With<PrimaryWindow>>,
-	mouse: Res<Input<MouseButton>>,
+	mut ev_w_action: EventWriter<BoardActionEvent>,
+	mut held_query: Query<(Entity, &Tile, &mut Transform, &mut Orientation, &mut Held)>,
+	tile_query: Query<(Entity, &Transform, &Orientation, (With<Tile>, Without<Held>, Without<Immovable>))>,
+	tilette_query: Query<(&Parent, &GlobalTransform, (With<Tilette>, Without<Tile>, Without<Held>))>,
+	pointer: Res<Pointer>,
+	actions: Res<Actions>,
 	asset_server: Res<AssetServer>,
 	audio: Res<Audio>,
 	volume_toggle: Res<VolumeToggle>,
 	thread_colliding: Res<ThreadColliding>,
+	board: Res<BoardConfig>,
+	occupancy: Res<BoardOccupancy>,
+	thread_cells: Res<ThreadCells>,
+	case_report_stack: Res<CaseReportStack>,
 ) {
-	let window = window_query.get_single().unwrap();
+	let turns = actions.just_pressed(Action::Rotate) as i32 - actions.just_pressed(Action::RotateBack) as i32;
+	let flip = actions.just_pressed(Action::Flip);
+	// Dragging with the mouse drops on release, the board cursor picks up and drops with separate presses
+	let drop = if pointer.snapped {actions.just_pressed(Action::Select)} else {actions.just_released(Action::Select)};
+	let holding = !held_query.is_empty();
 	
-	for (tile_entity, children, mut tile_pos, held) in held_query.iter_mut() {
-		if let Some(cursor_pos) = window.cursor_position() {
-			let cursor_pos = cursor_to_screen(cursor_pos, window);
+	for (tile_entity, tile, mut tile_pos, mut orientation, mut held) in held_query.iter_mut() {
+		if !orientation.locked && (turns != 0 || flip) {
+			// Keep the grabbed part of the tile under the cursor
+			if flip {
+				orientation.mirrored = !orientation.mirrored;
+				orientation.rotation = (4 - orientation.rotation) % 4;
+				held.offset.x = -held.offset.x;
+			}
+			orientation.rotate(turns);
+			for _ in 0..turns.rem_euclid(4) {
+				held.offset = Vec2::new(-held.offset.y, held.offset.x);
+			}
+			orientation.apply(&mut tile_pos);
+		}
+		if let Some(cursor_pos) = pointer.pos {
 			tile_pos.translation.x = cursor_pos.x + held.offset.x;
 			tile_pos.translation.y = cursor_pos.y + held.offset.y;
 
-			if mouse.just_released(MouseButton::Left) {
-				for (grid_pos, _) in grid_query.iter() {
-					if (cursor_pos.x - grid_pos.translation.x).abs() < GRID_SIZE.x/2.0 
-					&& (cursor_pos.y - grid_pos.translation.y).abs() < GRID_SIZE.y/2.0 
-					&& !thread_colliding.0 {
-						tile_pos.translation = Vec3::new(
-							(tile_pos.translation.x/40.0).round() * 40.0,
-							(tile_pos.translation.y/40.0).round() * 40.0,
-							tile_pos.translation.z,
-						);
-						for &child in children.iter() {
-							if let Ok((_, tilette_pos, _)) = tilette_query.get(child) {
-								if (tilette_pos.translation().x - grid_pos.translation.x).abs() > GRID_SIZE.x/2.0
-								|| (tilette_pos.translation().y - grid_pos.translation.y).abs() > GRID_SIZE.y/2.0 {
-									tile_pos.translation = held.origin;
-								} 
-							}
-						}
-					} else if tile_pos.translation.y > -50.0 - 80.0 || thread_colliding.0 {
-						tile_pos.translation = held.origin
-					}
-				}
-				let mut iter = tilette_query.iter_combinations();
-				while let Some([
-					(_, pos_a, _),
-					(_, pos_b, _),
-				]) = iter.fetch_next() {
-					if pos_a.translation().y > -50.0 - 80.0 && pos_b.translation().y > -50.0 - 80.0 
-					&& (pos_a.translation().x - pos_b.translation().x).abs() < CELL_SIZE/1.5
-					&& (pos_a.translation().y - pos_b.translation().y).abs() < CELL_SIZE/1.5 {
-						tile_pos.translation = held.origin;
-					}
+			if drop {
+				let placement = check_placement(tile_entity, tile.0, &orientation, &tile_pos, cursor_pos,
+					&board, &occupancy, &thread_cells, thread_colliding.0);
+				tile_pos.translation = placement.transform.translation;
+				// Refused drops go back the way they were picked up
+				if !placement.valid {
+					tile_pos.translation = held.origin;
+					*orientation = held.orientation;
+					orientation.apply(&mut tile_pos);
 				}
 				commands.entity(tile_entity).remove::<Held>();
+				if tile_pos.translation != held.origin || *orientation != held.orientation {
+					ev_w_action.send(BoardActionEvent(BoardAction::MoveTile));
+				}
 				if volume_toggle.sfx{
 					audio.play(asset_server.load("sounds/basic_haptic.ogg")).with_volume(SFX_VOLUME);
 				}
 			}
 		}
 	}
-	if mouse.just_pressed(MouseButton::Left) {
-		if let Some(cursor_pos) = window.cursor_position() {
-			let cursor_pos = cursor_to_screen(cursor_pos, window);
+	// Pinned case reports sit over the board and take the click
+	if actions.just_pressed(Action::Select) && !holding && !case_report_stack.hovered {
+		if let Some(cursor_pos) = pointer.pos {
 			for (parent, tilette_pos, _) in tilette_query.iter() {
 				// println!("Cursor: {}", cursor_pos);
 				// println!("tilette_pos.translation: {}", tilette_pos.translation().xy());
 				if (cursor_pos.x - tilette_pos.translation().x).abs() < H_CELL_SIZE 
 				&& (cursor_pos.y - tilette_pos.translation().y).abs() < H_CELL_SIZE {
-					if let Ok((tile_entity, tile_pos, _)) = tile_query.get(parent.get()) {
+					if let Ok((tile_entity, tile_pos, orientation, _)) = tile_query.get(parent.get()) {
 						commands.entity(tile_entity).insert(Held{
 							origin: Vec3::new(
 								tile_pos.translation.x,
@@ -163,6 +232,7 @@ fn drag_and_drop(
 								tile_pos.translation.x - cursor_pos.x,
 								tile_pos.translation.y - cursor_pos.y,
 							),
+							orientation: *orientation,
 						});
 						break;
 					}
@@ -170,4 +240,180 @@ fn drag_and_drop(
 			}
 		}
 	}
-}
\ No newline at end of file
+}
+
+// Mirroring lays the tilettes and tack out the other way round on the tile
+fn orient_tiles(
+	mut tile_query: Query<(&Tile, &Orientation, &mut Sprite, &Children), Changed<Orientation>>,
+	mut tilette_query: Query<(&Tilette, &mut Transform), Without<Tile>>,
+	mut tack_query: Query<(&Tack, &mut Transform), (Without<Tile>, Without<Tilette>)>,
+) {
+	for (tile, orientation, mut sprite, children) in tile_query.iter_mut() {
+		orientation.apply_art(tile.0, &mut sprite);
+		for &child in children.iter() {
+			if let Ok((tilette, mut tilette_pos)) = tilette_query.get_mut(child) {
+				tilette_pos.translation = orientation.offset(tilette.0).extend(tilette_pos.translation.z);
+			}
+			if let Ok((tack, mut tack_pos)) = tack_query.get_mut(child) {
+				tack_pos.translation = orientation.offset(tack.cell).extend(tack_pos.translation.z);
+			}
+		}
+	}
+}
+
+// Tiles on the board, or carried over it, are drawn by the board camera and the rest by the fixed view
+fn sort_tile_layers(
+	mut tile_query: Query<(&Transform, &Children, &mut RenderLayers, Has<Held>), With<Tile>>,
+	mut child_query: Query<&mut RenderLayers, Without<Tile>>,
+	pointer: Res<Pointer>,
+	board: Res<BoardConfig>,
+) {
+	for (tile_pos, children, mut layers, held) in tile_query.iter_mut() {
+		let layer = if held {pointer.layer()} else {board.layer_at(tile_pos.translation.xy())};
+		if *layers == layer {continue;};
+		*layers = layer;
+		for &child in children.iter() {
+			if let Ok(mut child_layers) = child_query.get_mut(child) {
+				*child_layers = layer;
+			}
+		}
+	}
+}
+
+fn update_occupancy(
+	mut occupancy: ResMut<BoardOccupancy>,
+	mut removed_tiles: RemovedComponents<Tile>,
+	mut dropped_tiles: RemovedComponents<Held>,
+	changed_query: Query<Entity, (With<Tile>, Or<(Changed<Transform>, Changed<Orientation>, Added<Held>)>)>,
+	tile_query: Query<(Entity, &Tile, &Orientation, &Transform, Has<Held>)>,
+	board: Res<BoardConfig>,
+) {
+	for tile_entity in removed_tiles.read() {
+		occupancy.remove(tile_entity);
+	}
+	// A new board starts empty and every tile gets placed again
+	let resized = board.is_changed();
+	if resized {
+		*occupancy = BoardOccupancy::new(board.width, board.height);
+	}
+	let changed: Vec<Entity> = if resized {tile_query.iter().map(|(tile_entity, _, _, _, _)| tile_entity).collect()} else {changed_query.iter().collect()};
+	for tile_entity in changed.into_iter().chain(dropped_tiles.read()) {
+		let Ok((_, tile, orientation, tile_pos, held)) = tile_query.get(tile_entity) else {continue};
+		// Held tiles don't cover anything until they're dropped
+		let cells = if held {Vec::new()} else {
+			tilette_locations(tile.0, orientation, tile_pos).into_iter().filter_map(|loc| board.cell_at(loc)).collect()
+		};
+		occupancy.place(tile_entity, cells);
+	}
+}
+
+// Ghost the held tile where it would snap and note the cells it would cover
+fn preview_placement(
+	mut commands: Commands,
+	mut preview: ResMut<PlacementPreview>,
+	mut ghost_query: Query<(Entity, &mut Transform, &mut Handle<Image>, &mut Sprite, (With<PlacementGhost>, Without<Held>))>,
+	held_query: Query<(Entity, &Tile, &Orientation, &Transform, &Handle<Image>, (With<Held>, Without<PlacementGhost>))>,
+	pointer: Res<Pointer>,
+	board: Res<BoardConfig>,
+	occupancy: Res<BoardOccupancy>,
+	thread_cells: Res<ThreadCells>,
+	thread_colliding: Res<ThreadColliding>,
+) {
+	let placement = match (held_query.get_single(), pointer.pos) {
+		(Ok((tile_entity, tile, orientation, tile_pos, texture, _)), Some(cursor_pos)) => {
+			let placement = check_placement(tile_entity, tile.0, orientation, tile_pos, cursor_pos,
+				&board, &occupancy, &thread_cells, thread_colliding.0);
+			if placement.on_board {Some((placement, tile.0, *orientation, texture.clone()))} else {None}
+		}
+		_ => None,
+	};
+
+	let Some((placement, tile_type, orientation, texture)) = placement else {
+		for (ghost_entity, _, _, _, _) in ghost_query.iter() {
+			commands.entity(ghost_entity).despawn_recursive();
+		}
+		if !preview.cells.is_empty() {
+			preview.cells.clear();
+		}
+		return;
+	};
+
+	let mut ghost_pos = placement.transform;
+	ghost_pos.translation.z = 290.0;
+	match ghost_query.get_single_mut() {
+		Ok((_, mut transform, mut ghost_texture, mut sprite, _)) => {
+			*transform = ghost_pos;
+			*ghost_texture = texture;
+			orientation.apply_art(tile_type, &mut sprite);
+		}
+		Err(_) => {
+			let mut sprite = Sprite {
+				custom_size: Some(Vec2::new(CELL_SIZE*4.0, CELL_SIZE*4.0)),
+				color: Color::rgba(1.0, 1.0, 1.0, 0.4),
+				..default()
+			};
+			orientation.apply_art(tile_type, &mut sprite);
+			commands.spawn((SpriteBundle{
+				transform: ghost_pos,
+				texture: texture,
+				sprite: sprite,
+				..default()
+				},
+				PlacementGhost,
+				BOARD_LAYER,
+				RemoveOnReset,
+			));
+		}
+	}
+
+	// A refused drop shows every covered cell as blocked
+	let cells: Vec<((usize, usize), bool)> = placement.cells.iter()
+		.map(|(cell, free)| (*cell, *free && placement.valid))
+		.collect();
+	if cells != preview.cells {
+		preview.cells = cells;
+	}
+}
+
+// Tacks sit on their tile's cells, so hovering either finds the tile
+pub fn track_hovered_tile(
+	mut hovered_tile: ResMut<HoveredTile>,
+	tile_query: Query<(Entity, &Transform, &Tile, &Orientation)>,
+	held_query: Query<(), With<Held>>,
+	pointer: Res<Pointer>,
+	case_report_stack: Res<CaseReportStack>,
+) {
+	let hovered = pointer.pos
+		.filter(|_| held_query.is_empty() && !case_report_stack.hovered)
+		.and_then(|cursor_pos| tile_query.iter().find(|(_, tile_pos, tile, orientation)| {
+			tilette_locations(tile.0, orientation, tile_pos).iter().any(|loc| {
+				(cursor_pos.x - loc.x).abs() < CELL_SIZE/2.0
+				&& (cursor_pos.y - loc.y).abs() < CELL_SIZE/2.0
+			})
+		}))
+		.map(|(tile_entity, _, _, _)| tile_entity);
+	if hovered_tile.0 != hovered {
+		hovered_tile.0 = hovered;
+	}
+}
+
+// Repaint the grid highlights only when thread crossings or the placement preview change
+fn update_highlights(
+	mut highlight_query: Query<(&Highlight, &mut Sprite)>,
+	added_query: Query<(), Added<Highlight>>,
+	thread_collisions: Res<ThreadCollisions>,
+	preview: Res<PlacementPreview>,
+) {
+	if !thread_collisions.is_changed() && !preview.is_changed() && added_query.is_empty() {return;};
+	for (highlight, mut highlight_sprite) in highlight_query.iter_mut() {
+		let color = match preview.cells.iter().find(|(cell, _)| *cell == highlight.0) {
+			Some((_, true)) => Color::rgba(0.0, 1.0, 0.0, 0.4),
+			Some((_, false)) => Color::rgba(1.0, 0.0, 0.0, 0.4),
+			None if thread_collisions.cells.contains(&highlight.0) => Color::rgba(1.0, 0.0, 0.0, 0.7),
+			None => Color::rgba(1.0, 1.0, 1.0, 0.0),
+		};
+		if highlight_sprite.color != color {
+			highlight_sprite.color = color;
+		}
+	}
+}
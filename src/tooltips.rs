@@ -0,0 +1,99 @@
+// Tooltips module for naming and describing the tile under the cursor
+use bevy::prelude::*;
+
+use crate::{derivables::*, setup::{generate_textbox, hint_section, title_section}, tiles::track_hovered_tile};
+
+pub struct TooltipsPlugin;
+
+impl Plugin for TooltipsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_systems(OnTransition{from: GameState::Boot, to: GameState::Game}, (
+				spawn_tooltip,
+			))
+			.add_systems(Update, (
+				update_tooltip.after(track_hovered_tile),
+			).run_if(in_state(GameState::Game)))
+			.add_systems(OnExit(GameState::Game), (
+				hide_tooltip,
+			))
+		;
+	}
+}
+
+// Seconds the cursor has to rest on a tile before its tooltip shows
+const HOVER_DELAY: f32 = 0.6;
+const TOOLTIP_SIZE: Vec2 = Vec2::new(360.0, 170.0);
+// Gap between the cursor and the corner of the tooltip
+const TOOLTIP_OFFSET: Vec2 = Vec2::new(20.0, -20.0);
+
+fn spawn_tooltip(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	let tooltip = generate_textbox(&mut commands, Vec3::new(0.0, 0.0, 960.0), TOOLTIP_SIZE, [
+		title_section(&asset_server, String::new()),
+		hint_section(&asset_server, String::new()),
+	].to_vec());
+	commands.entity(tooltip).insert((Tooltip, Visibility::Hidden));
+}
+
+fn update_tooltip(
+	mut tooltip_query: Query<(&mut Transform, &mut Visibility, &Children), With<Tooltip>>,
+	mut text_query: Query<&mut Text>,
+	tile_query: Query<&Tile>,
+	hovered_tile: Res<HoveredTile>,
+	pointer: Res<Pointer>,
+	mut hovered: Local<Option<(Entity, f32)>>,
+	time: Res<Time>,
+) {
+	let Ok((mut tooltip_pos, mut visibility, children)) = tooltip_query.get_single_mut() else {return};
+	let cursor_pos = pointer.ui;
+
+	let target = hovered_tile.0.and_then(|tile_entity| tile_query.get(tile_entity).ok().map(|tile| (tile_entity, tile.0)));
+	let (Some(cursor_pos), Some((tile_entity, tile_type))) = (cursor_pos, target) else {
+		*hovered = None;
+		*visibility = Visibility::Hidden;
+		return;
+	};
+	// Moving onto another tile restarts the wait
+	match *hovered {
+		Some((entity, _)) if entity == tile_entity => (),
+		_ => {
+			*hovered = Some((tile_entity, time.elapsed_seconds()));
+			*visibility = Visibility::Hidden;
+		}
+	}
+	let Some((_, since)) = *hovered else {return};
+	if time.elapsed_seconds() - since < HOVER_DELAY {return;};
+
+	if *visibility == Visibility::Hidden {
+		for &child in children.iter() {
+			if let Ok(mut text) = text_query.get_mut(child) {
+				text.sections[0].value = format!("{}\n", tile_type.get_name());
+				text.sections[1].value = tile_type.get_description();
+			}
+		}
+		*visibility = Visibility::Visible;
+	}
+
+	// Hang off the cursor but stay on screen
+	let half_screen = VIEW_SIZE / 2.0;
+	let half_size = TOOLTIP_SIZE / 2.0;
+	let mut centre = cursor_pos + TOOLTIP_OFFSET + Vec2::new(half_size.x, -half_size.y);
+	if centre.x + half_size.x > half_screen.x {
+		centre.x = cursor_pos.x - TOOLTIP_OFFSET.x - half_size.x;
+	}
+	if centre.y - half_size.y < -half_screen.y {
+		centre.y = cursor_pos.y - TOOLTIP_OFFSET.y + half_size.y;
+	}
+	tooltip_pos.translation = centre.extend(tooltip_pos.translation.z);
+}
+
+fn hide_tooltip(
+	mut tooltip_query: Query<&mut Visibility, With<Tooltip>>,
+) {
+	for mut visibility in tooltip_query.iter_mut() {
+		*visibility = Visibility::Hidden;
+	}
+}